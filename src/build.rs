@@ -1,64 +1,551 @@
 use std::{
-    io::{self, Read, Seek, Write},
-    path,
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
 };
 
 use anyhow::Result;
-use console::style;
-use futures_util::StreamExt;
+use futures_util::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
 
-use crate::{context, services, utils::file::Empty};
+use crate::{constants, context, docker, presentation, releases, services};
 
+/// Files of the docker context, keyed by their path inside the archive
+type ContextFiles = BTreeMap<PathBuf, PathBuf>;
+
+/// Size of the chunks the context archive is sent to the builder in
+const CONTEXT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks waiting to be sent, bounds the memory used while the builder is slower
+const CONTEXT_CHANNEL_CAPACITY: usize = 16;
+
+/// Builds the image with the daemon of the deploy target,
+/// or with the local one and sends it to the target afterwards.
+/// When the context didn't change since a previous build, its image is reused
+/// and nothing is uploaded
 pub async fn build_app_service_image(
     context: &context::Context,
     app_service: &services::app::AppService,
     docker: &bollard::Docker,
+    release: &str,
 ) -> Result<String> {
-    let bytes = create_cwd_tar(context)?;
+    let files = context_files(context, app_service)?;
+    let mut options = app_service.to_image_build_config(release)?;
+    let context_hash = hash_context(&options, app_service.build().target(), &files)?;
+    options.labels.get_or_insert_with(HashMap::new).insert(
+        releases::CONTEXT_HASH_LABEL.to_owned(),
+        context_hash.clone(),
+    );
+
+    // builds without cache always run
+    let reusable_image_id = if options.nocache {
+        None
+    } else {
+        find_image_with_context(docker, app_service.image_name(), &context_hash).await?
+    };
+
+    if let Some(image_id) = reusable_image_id {
+        presentation::print_image_context_unchanged(app_service.image_name());
+        tag_app_service_image(app_service, docker, &image_id, release).await?;
+
+        return Ok(image_id);
+    }
+
+    if !context.should_build_locally() {
+        return build_image(context, app_service, docker, options, &files).await;
+    }
+
+    let local_docker = docker::get_default_docker_client().await?;
+    let image_id = build_image(context, app_service, &local_docker, options, &files).await?;
+
+    let reference = format!("{}:{release}", app_service.image_name());
+    let result = send_image(context, &local_docker, docker, &reference).await;
+
+    // the release lives on the server, parents are kept as build cache
+    local_docker
+        .remove_image(
+            &reference,
+            Some(bollard::query_parameters::RemoveImageOptions {
+                noprune: true,
+                ..Default::default()
+            }),
+            None,
+        )
+        .await
+        .ok();
+
+    result?;
+
+    Ok(image_id)
+}
+
+/// Tags an already built image as the release of the app
+pub async fn tag_app_service_image(
+    app_service: &services::app::AppService,
+    docker: &bollard::Docker,
+    image_id: &str,
+    release: &str,
+) -> Result<()> {
+    docker
+        .tag_image(
+            image_id,
+            Some(bollard::query_parameters::TagImageOptions {
+                repo: Some(app_service.image_name().to_owned()),
+                tag: Some(release.to_owned()),
+            }),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Pulls the prebuilt image and tags it as the release,
+/// so releases and rollbacks work the same way as for built images
+pub async fn pull_app_service_image(
+    app_service: &services::app::AppService,
+    docker: &bollard::Docker,
+    image: &str,
+    release: &str,
+) -> Result<()> {
+    let reference = with_default_tag(image);
+
+    let mut stream = docker.create_image(
+        Some(bollard::query_parameters::CreateImageOptions {
+            from_image: Some(reference.clone()),
+            ..Default::default()
+        }),
+        None,
+        registry_credentials(&reference),
+    );
+
+    while let Some(info) = stream.next().await {
+        if let Some(error) = info?.error {
+            anyhow::bail!("Could not pull {reference}: {error}");
+        }
+    }
+
+    docker
+        .tag_image(
+            &reference,
+            Some(bollard::query_parameters::TagImageOptions {
+                repo: Some(app_service.image_name().to_owned()),
+                tag: Some(release.to_owned()),
+            }),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Without a tag docker would pull all tags of the repository
+fn with_default_tag(image: &str) -> String {
+    let name = image.rsplit('/').next().unwrap_or(image);
+
+    if name.contains(':') || name.contains('@') {
+        image.to_owned()
+    } else {
+        format!("{image}:{}", releases::CURRENT_TAG)
+    }
+}
+
+/// Credentials are taken from the env, so they never end up in the config
+fn registry_credentials(image: &str) -> Option<bollard::auth::DockerCredentials> {
+    let username = std::env::var(constants::REGISTRY_USERNAME_ENV).ok()?;
+    let password = std::env::var(constants::REGISTRY_PASSWORD_ENV).ok()?;
+
+    Some(bollard::auth::DockerCredentials {
+        username: Some(username),
+        password: Some(password),
+        serveraddress: registry_of(image).map(str::to_owned),
+        ..Default::default()
+    })
+}
+
+/// Registry host of the image, `None` for Docker Hub
+fn registry_of(image: &str) -> Option<&str> {
+    let (host, _) = image.split_once('/')?;
+
+    (host.contains('.') || host.contains(':') || host == "localhost").then_some(host)
+}
+
+/// Exports the image from the local daemon and loads it into the remote one.
+/// The export is piped into the upload chunk by chunk, compressed on the way,
+/// so the image is never kept in memory and the progress follows the upload
+async fn send_image(
+    context: &context::Context,
+    local_docker: &bollard::Docker,
+    remote_docker: &bollard::Docker,
+    reference: &str,
+) -> Result<()> {
+    let total_size = local_docker
+        .inspect_image(reference)
+        .await?
+        .size
+        .unwrap_or_default()
+        .try_into()
+        .unwrap_or_default();
+
+    let encoder = context
+        .app_config()
+        .compress_image(context.override_context())
+        .then(|| flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast()));
+
+    presentation::print_image_uploading(reference, total_size);
+
+    let export = local_docker.export_image(reference);
+    let progress = UploadProgress::new(reference, total_size);
+
+    let body = futures::stream::unfold(Some((export, encoder, progress)), |state| async move {
+        let (mut export, mut encoder, mut progress) = state?;
+
+        loop {
+            let chunk = match export.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(error)) => return Some((Err(io::Error::other(error)), None)),
+                None => {
+                    progress.finish();
+
+                    // the gzip trailer is written when the encoder is finished
+                    let trailer = encoder.map(flate2::write::GzEncoder::finish)?;
+                    return Some((trailer.map(Into::into), None));
+                }
+            };
 
-    let mut stream = docker.build_image(
-        app_service.to_image_build_config()?,
+            progress.advance(chunk.len());
+
+            let Some(gzip) = encoder.as_mut() else {
+                return Some((Ok(chunk), Some((export, encoder, progress))));
+            };
+
+            if let Err(error) = gzip.write_all(&chunk) {
+                return Some((Err(error), None));
+            }
+
+            // the encoder buffers small inputs, empty chunks aren't sent
+            if !gzip.get_ref().is_empty() {
+                let compressed = std::mem::take(gzip.get_mut());
+                return Some((Ok(compressed.into()), Some((export, encoder, progress))));
+            }
+        }
+    });
+
+    let mut stream = remote_docker.import_image(
+        bollard::query_parameters::ImportImageOptions {
+            quiet: true,
+            ..Default::default()
+        },
+        bollard::body_try_stream(body),
         None,
-        Some(bytes.into()),
     );
 
+    while let Some(info) = stream.next().await {
+        info?;
+    }
+
+    presentation::print_image_uploaded(reference);
+
+    Ok(())
+}
+
+/// Progress of the image upload, redrawn whenever another percent is sent
+struct UploadProgress {
+    label: String,
+    uploaded: u64,
+    total: u64,
+    printed_percent: Option<u64>,
+}
+
+impl UploadProgress {
+    fn new(label: &str, total: u64) -> Self {
+        Self {
+            label: label.to_owned(),
+            uploaded: 0,
+            total,
+            printed_percent: None,
+        }
+    }
+
+    fn advance(&mut self, size: usize) {
+        self.uploaded += size as u64;
+
+        let percent = (self.uploaded * 100)
+            .checked_div(self.total)
+            .map(|percent| percent.min(100));
+
+        if percent != self.printed_percent {
+            presentation::print_image_upload_progress(&self.label, self.uploaded, self.total);
+            self.printed_percent = percent;
+        }
+    }
+
+    fn finish(&self) {
+        presentation::print_image_upload_progress_done();
+    }
+}
+
+/// Image of the app built from exactly the same context
+async fn find_image_with_context(
+    docker: &bollard::Docker,
+    image_name: &str,
+    context_hash: &str,
+) -> Result<Option<String>> {
+    let images = docker
+        .list_images(Some(bollard::query_parameters::ListImagesOptions {
+            filters: Some(HashMap::from([
+                ("reference".to_owned(), vec![image_name.to_owned()]),
+                (
+                    "label".to_owned(),
+                    vec![format!("{}={context_hash}", releases::CONTEXT_HASH_LABEL)],
+                ),
+            ])),
+            ..Default::default()
+        }))
+        .await?;
+
+    Ok(images.into_iter().next().map(|image| image.id))
+}
+
+async fn build_image(
+    context: &context::Context,
+    app_service: &services::app::AppService,
+    docker: &bollard::Docker,
+    options: bollard::query_parameters::BuildImageOptions,
+    files: &ContextFiles,
+) -> Result<String> {
+    let size = files
+        .values()
+        .map(|entry_path| fs::metadata(entry_path).map(|metadata| metadata.len()))
+        .sum::<io::Result<u64>>()?;
+
+    presentation::print_image_context_uploading(app_service.image_name(), files.len(), size);
+
+    // the archive is sent while it's written, so it's never kept in memory as a whole
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(CONTEXT_CHANNEL_CAPACITY);
+    let files = files.clone();
+    let writer = tokio::task::spawn_blocking(move || {
+        let result = write_context_tar(&files, ChannelWriter::new(sender.clone()));
+
+        // aborts the upload, so the builder doesn't get a truncated archive
+        if let Err(error) = &result {
+            sender
+                .blocking_send(Err(io::Error::new(error.kind(), error.to_string())))
+                .ok();
+        }
+
+        result
+    });
+
+    let body = futures::stream::poll_fn(move |cx| receiver.poll_recv(cx))
+        .map(|chunk| chunk.map(Into::into));
+
+    let mut output = BuildOutput::new(context, app_service.image_name())?;
+    let result = read_build_output(
+        &mut output,
+        docker.build_image(options, None, Some(bollard::body_try_stream(body))),
+    )
+    .await;
+
+    match (result, writer.await?) {
+        (Ok(image_id), _) => Ok(image_id),
+        // the writer fails with a broken pipe when the builder stops reading after an error
+        (Err(_), Err(error)) if error.kind() != io::ErrorKind::BrokenPipe => {
+            Err(anyhow::Error::new(error).context("Could not archive the docker context"))
+        }
+        (Err(error), _) => Err(error),
+    }
+}
+
+async fn read_build_output(
+    output: &mut BuildOutput,
+    stream: impl Stream<Item = Result<bollard::models::BuildInfo, bollard::errors::Error>>,
+) -> Result<String> {
+    let mut stream = std::pin::pin!(stream);
     let mut image_id = None;
 
     while let Some(info) = stream.next().await {
-        // TODO: log info from build image
-        match info? {
-            bollard::models::BuildInfo {
+        match info {
+            Ok(bollard::models::BuildInfo {
                 aux: Some(image_id_inner),
                 ..
-            } => {
+            }) => {
                 image_id = image_id_inner.id;
             }
-            bollard::models::BuildInfo {
+            Ok(bollard::models::BuildInfo {
                 stream: Some(stream),
                 ..
-            } if !stream.trim().is_empty() => {
-                let formatted_stream = if stream.ends_with('\n') {
-                    stream.clone()
-                } else {
-                    format!("{}\n", stream)
-                };
-
-                print!("{}", style(formatted_stream).dim());
+            }) => output.stream(&stream)?,
+            Ok(bollard::models::BuildInfo {
+                status: Some(status),
+                ..
+            }) => output.status(&status)?,
+            Ok(_) => {}
+            Err(bollard::errors::Error::DockerStreamError { error }) => {
+                return Err(output.error(&error));
             }
-            _ => {}
+            Err(error) => return Err(output.error(&error.to_string())),
+        }
+    }
+
+    output.finish();
+
+    image_id.ok_or_else(|| output.error("The builder did not report the id of the image"))
+}
+
+/// Sends the written bytes into the channel in chunks
+struct ChannelWriter {
+    sender: tokio::sync::mpsc::Sender<io::Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn new(sender: tokio::sync::mpsc::Sender<io::Result<Vec<u8>>>) -> Self {
+        Self {
+            sender,
+            buffer: Vec::with_capacity(CONTEXT_CHUNK_SIZE),
+        }
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        if self.buffer.len() >= CONTEXT_CHUNK_SIZE {
+            self.flush()?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
         }
+
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CONTEXT_CHUNK_SIZE));
+
+        self.sender
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "The builder stopped reading"))
     }
+}
 
-    image_id.ok_or_else(|| anyhow::anyhow!("Failed to build image"))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BuildOutputMode {
+    /// Only the step counter
+    Quiet,
+
+    /// Output of the build steps
+    Normal,
+
+    /// Also pull progress of base images and the files sent to the builder
+    Verbose,
 }
 
-fn create_cwd_tar(context: &context::Context) -> Result<Vec<u8>> {
-    let docker_context = context.app_config().context(context.override_context());
+impl BuildOutputMode {
+    fn from_context(context: &context::Context) -> Self {
+        if context.args().verbose() {
+            BuildOutputMode::Verbose
+        } else if context.args().quiet_build() {
+            BuildOutputMode::Quiet
+        } else {
+            BuildOutputMode::Normal
+        }
+    }
+}
 
-    let mut bytes = Vec::<u8>::new();
-    let mut archive = tar::Builder::new(&mut bytes);
+/// Prints the build output according to the mode
+/// and writes all of it to the build log
+struct BuildOutput {
+    label: String,
+    mode: BuildOutputMode,
+    log: fs::File,
 
-    let walker = create_walker(context);
+    /// Last `Step N/M : INSTRUCTION` line, shown when the build fails
+    step: Option<String>,
+}
+
+impl BuildOutput {
+    fn new(context: &context::Context, label: &str) -> Result<Self> {
+        let log_path = Path::new(constants::BUILD_LOG_PATH);
+
+        if let Some(log_dir) = log_path.parent() {
+            fs::create_dir_all(log_dir)?;
+        }
+
+        Ok(Self {
+            label: label.to_owned(),
+            mode: BuildOutputMode::from_context(context),
+            log: fs::File::create(log_path)?,
+            step: None,
+        })
+    }
+
+    fn stream(&mut self, stream: &str) -> Result<()> {
+        self.log.write_all(stream.as_bytes())?;
+
+        for line in stream.lines().filter(|line| !line.trim().is_empty()) {
+            let is_step = line.starts_with("Step ");
+
+            if is_step {
+                self.step = Some(line.to_owned());
+            }
+
+            match self.mode {
+                BuildOutputMode::Quiet if is_step => {
+                    let counter = line.split(" : ").next().unwrap_or(line);
+                    presentation::print_build_step(&self.label, counter);
+                }
+                BuildOutputMode::Quiet => {}
+                BuildOutputMode::Normal | BuildOutputMode::Verbose => {
+                    presentation::print_build_line(line);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn status(&mut self, status: &str) -> Result<()> {
+        writeln!(self.log, "{status}")?;
+
+        if self.mode == BuildOutputMode::Verbose {
+            presentation::print_build_line(status);
+        }
+
+        Ok(())
+    }
+
+    fn finish(&self) {
+        if self.mode == BuildOutputMode::Quiet {
+            presentation::print_build_steps_done();
+        }
+    }
+
+    /// Shows the error with the step it happened at, the returned error is meant to be bailed with
+    fn error(&mut self, error: &str) -> anyhow::Error {
+        writeln!(self.log, "{error}").ok();
+
+        self.finish();
+        presentation::print_build_error(&self.label, self.step.as_deref(), error);
+        presentation::print_build_log_saved(constants::BUILD_LOG_PATH);
+
+        anyhow::anyhow!("Failed to build image: {}", error.trim())
+    }
+}
+
+/// Collects the files of the docker context, respecting the ignore files
+fn context_files(
+    context: &context::Context,
+    app_service: &services::app::AppService,
+) -> Result<ContextFiles> {
+    let docker_context = app_service.docker_context();
+
+    let mut files = ContextFiles::new();
+
+    let walker = create_walker(context, docker_context);
 
     for entry in walker.filter_map(Result::ok) {
         let metadata = entry.metadata()?;
@@ -70,45 +557,88 @@ fn create_cwd_tar(context: &context::Context) -> Result<Vec<u8>> {
         let entry_path = entry.path();
         let stripped_path = entry_path.strip_prefix(docker_context)?;
 
-        archive.append_path_with_name(entry_path, stripped_path)?;
+        files.insert(stripped_path.to_owned(), entry_path.to_owned());
     }
 
-    for file_name in get_always_include_files(context) {
-        let relative_path = context
-            .config_dir_relative_to_docker_context()
-            .join(&file_name);
+    for file_name in get_always_include_files(context, app_service) {
+        let relative_path = app_service.relative_path().join(&file_name);
 
-        let entry_path = path::PathBuf::from(relative_path);
-        println!("Entry path: {}", entry_path.display());
+        let entry_path = relative_path;
         let stripped_path = entry_path.strip_prefix(docker_context)?;
-        println!("Stripped path: {}", stripped_path.display());
 
-        archive.append_path_with_name(&entry_path, stripped_path)?;
+        files.insert(stripped_path.to_owned(), entry_path);
+    }
+
+    if context.args().verbose() {
+        for (archive_path, entry_path) in &files {
+            presentation::print_build_context_file(entry_path, archive_path);
+        }
     }
 
-    archive.into_inner()?;
+    Ok(files)
+}
 
-    let mut compressed_file = tempfile::tempfile()?;
-    let mut encoder =
-        flate2::write::GzEncoder::new(&mut compressed_file, flate2::Compression::default());
+/// Hash of the paths, permissions and contents of the context files
+/// and of the options the image is built with
+fn hash_context(
+    options: &bollard::query_parameters::BuildImageOptions,
+    target: Option<&str>,
+    files: &ContextFiles,
+) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    // maps are sorted, so the hash doesn't depend on their order
+    let build_args = options
+        .buildargs
+        .iter()
+        .flatten()
+        .collect::<BTreeMap<_, _>>();
+    let labels = options
+        .labels
+        .iter()
+        .flatten()
+        .filter(|(key, _)| key.as_str() != releases::RELEASE_LABEL)
+        .collect::<BTreeMap<_, _>>();
+
+    hasher.update(
+        serde_json::json!({
+            "dockerfile": options.dockerfile,
+            "target": target,
+            "platform": options.platform,
+            "build_args": build_args,
+            "labels": labels,
+            "cache_from": options.cachefrom,
+        })
+        .to_string()
+        .as_bytes(),
+    );
 
-    // TODO: create initial tar file and compress it by reading it in chunks
-    encoder.write_all(&bytes)?;
-    encoder.finish()?;
+    for (archive_path, entry_path) in files {
+        let mut file_hasher = Sha256::new();
+        io::copy(&mut fs::File::open(entry_path)?, &mut file_hasher)?;
+
+        hasher.update(archive_path.to_string_lossy().as_bytes());
+        hasher.update(fs::metadata(entry_path)?.permissions().mode().to_le_bytes());
+        hasher.update(file_hasher.finalize());
+    }
 
-    drop(bytes);
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-    let mut compressed_bytes = Vec::new();
-    compressed_file.seek(io::SeekFrom::Start(0))?;
-    compressed_file.read_to_end(&mut compressed_bytes)?;
+/// Files are written through the gzip encoder as they're archived,
+/// so neither the archive nor its compressed form is kept in memory
+fn write_context_tar(files: &ContextFiles, writer: impl Write) -> io::Result<()> {
+    let encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+    let mut archive = tar::Builder::new(encoder);
 
-    let _ = compressed_file.empty();
+    for (archive_path, entry_path) in files {
+        archive.append_path_with_name(entry_path, archive_path)?;
+    }
 
-    Ok(compressed_bytes)
+    archive.into_inner()?.finish()?.flush()
 }
 
-fn create_walker(context: &context::Context) -> ignore::Walk {
-    let docker_context = context.app_config().context(context.override_context());
+fn create_walker(context: &context::Context, docker_context: &str) -> ignore::Walk {
     let mut builder = ignore::WalkBuilder::new(docker_context);
 
     for ignore_file in context
@@ -118,16 +648,33 @@ fn create_walker(context: &context::Context) -> ignore::Walk {
         builder.add_ignore(ignore_file);
     }
 
+    // the build log changes on every build, so it would always change the context hash
+    let log_dir = Path::new(constants::BUILD_LOG_PATH)
+        .parent()
+        .and_then(|log_dir| log_dir.canonicalize().ok());
+
     builder
         .hidden(false)
         .ignore(false)
         .git_ignore(false)
         .git_global(false)
+        .filter_entry(move |entry| {
+            let is_log_dir = entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir())
+                && log_dir.is_some()
+                && entry.path().canonicalize().ok() == log_dir;
+
+            !is_log_dir
+        })
         .build()
 }
 
-fn get_always_include_files(context: &context::Context) -> Vec<String> {
-    let dockerfile = context.app_config().dockerfile(context.override_context());
+fn get_always_include_files(
+    context: &context::Context,
+    app_service: &services::app::AppService,
+) -> Vec<String> {
+    let dockerfile = app_service.dockerfile();
     let ignore_files = context
         .app_config()
         .ignore_files(context.override_context());
@@ -140,3 +687,60 @@ fn get_always_include_files(context: &context::Context) -> Vec<String> {
 
     files
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_context_tar() {
+        let files = ContextFiles::from([
+            (PathBuf::from("Cargo.toml"), PathBuf::from("Cargo.toml")),
+            (PathBuf::from("app/Cargo.toml"), PathBuf::from("Cargo.toml")),
+        ]);
+
+        let mut bytes = vec![];
+        write_context_tar(&files, &mut bytes).unwrap();
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(bytes.as_slice()));
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = String::new();
+                io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+
+                (entry.path().unwrap().into_owned(), contents)
+            })
+            .collect::<Vec<_>>();
+
+        let contents = fs::read_to_string("Cargo.toml").unwrap();
+
+        assert_eq!(
+            entries,
+            [
+                (PathBuf::from("Cargo.toml"), contents.clone()),
+                (PathBuf::from("app/Cargo.toml"), contents),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_reference() {
+        assert_eq!(with_default_tag("app"), "app:latest");
+        assert_eq!(
+            with_default_tag("localhost:5000/app"),
+            "localhost:5000/app:latest"
+        );
+        assert_eq!(
+            with_default_tag("ghcr.io/org/app:1.2"),
+            "ghcr.io/org/app:1.2"
+        );
+
+        assert_eq!(registry_of("ghcr.io/org/app:1.2"), Some("ghcr.io"));
+        assert_eq!(registry_of("localhost:5000/app"), Some("localhost:5000"));
+        assert_eq!(registry_of("org/app"), None);
+        assert_eq!(registry_of("app"), None);
+    }
+}
@@ -1,7 +1,9 @@
+use std::{convert::Infallible, path::PathBuf, str::FromStr, time::Duration};
+
 use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 
-use crate::{config, constants, services::ServiceKind};
+use crate::{config, constants, context, services::ServiceKind, utils};
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -15,6 +17,19 @@ pub struct Args {
     #[clap(short, long, default_value = constants::DEFAULT_NAMESPACE)]
     pub namespace: String,
 
+    /// Show only the step counter while building images
+    #[clap(
+        long,
+        global = true,
+        default_value_t = false,
+        conflicts_with = "verbose"
+    )]
+    pub quiet_build: bool,
+
+    /// Show all build output, including base image pulls and the files sent to the builder
+    #[clap(long, global = true, default_value_t = false)]
+    pub verbose: bool,
+
     #[clap(subcommand)]
     pub command: Command,
 }
@@ -31,6 +46,30 @@ impl Args {
     pub fn command(&self) -> &Command {
         &self.command
     }
+
+    pub fn quiet_build(&self) -> bool {
+        self.quiet_build
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    /// Output is meant for scripts, so nothing else must be printed to stdout
+    pub fn is_json_output(&self) -> bool {
+        matches!(
+            self.command(),
+            Command::Deploy {
+                command: Some(DeployCommand::Status { json: true }),
+                ..
+            } | Command::Run {
+                command: Some(RunCommand::Status { json: true }),
+                ..
+            } | Command::Dev {
+                command: Some(DevCommand::Status { json: true }),
+            }
+        )
+    }
 }
 
 #[derive(Debug, Subcommand)]
@@ -75,6 +114,11 @@ pub enum Command {
         /// Watch for file changes and restart the application
         #[clap(short, long, default_value_t = false)]
         watch: bool,
+
+        /// Start the proxy on free ports, serving `[[proxy]]` domains
+        /// under `*.localhost` with locally issued certificates
+        #[clap(long, default_value_t = false)]
+        proxy: bool,
     },
 
     /// Run only the dependencies of the application locally
@@ -85,6 +129,60 @@ pub enum Command {
         #[clap(subcommand)]
         command: Option<DevCommand>,
     },
+
+    /// List projects and namespaces deployed with dploy on the host
+    Ls {
+        #[clap(flatten)]
+        host: HostArgs,
+    },
+
+    /// Remove dangling images of dploy builds and stale namespaces on the host
+    Gc {
+        #[clap(flatten)]
+        host: HostArgs,
+
+        /// Also remove namespaces whose containers have all been stopped
+        /// for longer than this, e.g. `7d` or `12h`
+        #[clap(long, value_parser = utils::duration::parse)]
+        stale: Option<Duration>,
+
+        /// Print what would be removed without removing anything
+        #[clap(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+/// Docker host of commands covering all projects, local when no host is given
+#[derive(Debug, clap::Args)]
+pub struct HostArgs {
+    /// Host of the remote server. Omit to use the local docker
+    #[clap(index = 1)]
+    host: Option<String>,
+
+    /// Port of the remote server
+    #[clap(short, long, default_value_t = 22)]
+    port: u16,
+
+    /// Username of the remote server
+    #[clap(short, long, default_value = "root")]
+    username: String,
+
+    /// Path to the private key file
+    #[clap(short, long)]
+    keyfile: Option<String>,
+}
+
+impl HostArgs {
+    pub fn ssh_credentials(&self) -> Option<context::SshCredentials> {
+        self.host.as_ref().map(|host| {
+            context::SshCredentials::new(
+                host.clone(),
+                self.port,
+                self.username.clone(),
+                self.keyfile.clone().map(PathBuf::from),
+            )
+        })
+    }
 }
 
 impl From<&Command> for config::OverrideRuleCommand {
@@ -93,6 +191,9 @@ impl From<&Command> for config::OverrideRuleCommand {
             Command::Deploy { .. } => config::OverrideRuleCommand::Deploy,
             Command::Run { .. } => config::OverrideRuleCommand::Run,
             Command::Dev { .. } => config::OverrideRuleCommand::Dev,
+            Command::Ls { .. } | Command::Gc { .. } => {
+                unreachable!("host commands don't read the config")
+            }
         }
     }
 }
@@ -103,6 +204,24 @@ pub enum DevCommand {
     #[clap(visible_alias = "s")]
     Stop,
 
+    /// Show the state of all services of the namespace
+    Status {
+        /// Print machine-readable JSON
+        #[clap(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// Remove containers and images of the namespace
+    Destroy {
+        /// Also delete persisted data, including generated secrets
+        #[clap(long, default_value_t = false)]
+        volumes: bool,
+
+        /// Don't ask for confirmation before deleting data
+        #[clap(short, long, default_value_t = false)]
+        yes: bool,
+    },
+
     /// Get logs of the specified service
     #[clap(visible_alias = "l")]
     Logs {
@@ -132,12 +251,14 @@ pub enum DevCommand {
 #[serde(rename_all = "snake_case")]
 pub enum DevLogsService {
     Postgres,
+    Keydb,
 }
 
 impl From<DevLogsService> for ServiceKind {
     fn from(value: DevLogsService) -> Self {
         match value {
             DevLogsService::Postgres => ServiceKind::Postgres,
+            DevLogsService::Keydb => ServiceKind::Keydb,
         }
     }
 }
@@ -146,7 +267,30 @@ impl From<DevLogsService> for ServiceKind {
 pub enum RunCommand {
     /// Stop the application
     #[clap(visible_alias = "s")]
-    Stop,
+    Stop {
+        /// App to stop, leaving the other apps and the dependencies running.
+        /// Omit to stop everything
+        #[clap(short, long)]
+        service: Option<ServiceSelector>,
+    },
+
+    /// Show the state of all services of the namespace
+    Status {
+        /// Print machine-readable JSON
+        #[clap(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// Remove containers and images of the namespace
+    Destroy {
+        /// Also delete persisted data, including generated secrets
+        #[clap(long, default_value_t = false)]
+        volumes: bool,
+
+        /// Don't ask for confirmation before deleting data
+        #[clap(short, long, default_value_t = false)]
+        yes: bool,
+    },
 
     /// Get logs of application container
     #[clap(visible_alias = "l")]
@@ -155,9 +299,9 @@ pub enum RunCommand {
         #[clap(short, long)]
         tail: Option<u64>,
 
-        /// Service to get logs from
+        /// Service or app to get logs from
         #[clap(short, long, default_value = "app")]
-        service: RunLogsService,
+        service: ServiceSelector,
     },
 
     /// Execute a command in the application container
@@ -167,33 +311,40 @@ pub enum RunCommand {
         #[clap(index = 1)]
         command: String,
 
-        /// Service to get logs from
+        /// Service or app to get logs from
         #[clap(short, long, default_value = "app")]
-        service: RunLogsService,
+        service: ServiceSelector,
     },
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum)]
-#[serde(rename_all = "snake_case")]
-pub enum RunLogsService {
-    App,
-    Postgres,
-}
-
-impl From<RunLogsService> for ServiceKind {
-    fn from(value: RunLogsService) -> Self {
-        match value {
-            RunLogsService::App => ServiceKind::App,
-            RunLogsService::Postgres => ServiceKind::Postgres,
-        }
-    }
-}
-
 #[derive(Debug, Subcommand)]
 pub enum DeployCommand {
     /// Stop the application
     #[clap(visible_alias = "s")]
-    Stop,
+    Stop {
+        /// App to stop, leaving the other apps and the dependencies running.
+        /// Omit to stop everything
+        #[clap(short, long)]
+        service: Option<ServiceSelector>,
+    },
+
+    /// Show the state of all services of the namespace
+    Status {
+        /// Print machine-readable JSON
+        #[clap(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// Remove containers and images of the namespace
+    Destroy {
+        /// Also delete persisted data, including generated secrets
+        #[clap(long, default_value_t = false)]
+        volumes: bool,
+
+        /// Don't ask for confirmation before deleting data
+        #[clap(short, long, default_value_t = false)]
+        yes: bool,
+    },
 
     /// Get logs of application container
     #[clap(visible_alias = "l")]
@@ -202,9 +353,9 @@ pub enum DeployCommand {
         #[clap(short, long)]
         tail: Option<u64>,
 
-        /// Service to get logs from
+        /// Service or app to get logs from
         #[clap(short, long, default_value = "app")]
-        service: DeployLogsService,
+        service: ServiceSelector,
     },
 
     /// Execute a command in the application container
@@ -214,27 +365,65 @@ pub enum DeployCommand {
         #[clap(index = 1)]
         command: String,
 
-        /// Service to get logs from
+        /// Service or app to get logs from
         #[clap(short, long, default_value = "app")]
-        service: DeployLogsService,
+        service: ServiceSelector,
+    },
+
+    /// Recreate the application container from a previous release without rebuilding
+    Rollback {
+        /// Release to roll back to. Omit to use the release before the current one
+        #[clap(index = 1)]
+        release: Option<String>,
+    },
+
+    /// List application releases available for rollback
+    Releases,
+
+    /// Manage the proxy shared by all applications on the host
+    Proxy {
+        #[clap(subcommand)]
+        command: ProxyCommand,
     },
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum)]
-#[serde(rename_all = "snake_case")]
-pub enum DeployLogsService {
-    App,
-    Postgres,
-    Proxy,
+#[derive(Debug, Subcommand)]
+pub enum ProxyCommand {
+    /// Show the state of the proxy and the applications using it
+    Status,
+
+    /// Restart the proxy container
+    Restart,
+
+    /// Stop and remove the proxy container
+    Remove,
 }
 
-impl From<DeployLogsService> for ServiceKind {
-    fn from(value: DeployLogsService) -> Self {
-        match value {
-            DeployLogsService::App => ServiceKind::App,
-            DeployLogsService::Postgres => ServiceKind::Postgres,
-            DeployLogsService::Proxy => ServiceKind::Proxy,
-        }
+/// Service selected with `-s`: `app` for the top-level app, a dependency,
+/// or the name of an app
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceSelector {
+    Kind(ServiceKind),
+    App(String),
+}
+
+impl FromStr for ServiceSelector {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "app" => ServiceSelector::Kind(ServiceKind::App),
+            "postgres" => ServiceSelector::Kind(ServiceKind::Postgres),
+            "keydb" => ServiceSelector::Kind(ServiceKind::Keydb),
+            "proxy" => ServiceSelector::Kind(ServiceKind::Proxy),
+            app_name => ServiceSelector::App(app_name.to_owned()),
+        })
+    }
+}
+
+impl From<DevLogsService> for ServiceSelector {
+    fn from(value: DevLogsService) -> Self {
+        ServiceSelector::Kind(value.into())
     }
 }
 
@@ -243,9 +432,10 @@ impl Command {
         use Command::*;
 
         match self {
-            Deploy { command, .. } => matches!(command, Some(DeployCommand::Stop)),
-            Run { command, .. } => matches!(command, Some(RunCommand::Stop)),
+            Deploy { command, .. } => matches!(command, Some(DeployCommand::Stop { .. })),
+            Run { command, .. } => matches!(command, Some(RunCommand::Stop { .. })),
             Dev { command, .. } => matches!(command, Some(DevCommand::Stop)),
+            Ls { .. } | Gc { .. } => false,
         }
     }
 
@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     env, fs,
     io::Write,
     path::Path,
@@ -11,26 +11,27 @@ use futures_util::TryStreamExt;
 use notify::Watcher;
 
 use crate::{
-    build, commands, context, docker, network,
+    build,
+    cli::ServiceSelector,
+    commands,
+    config::HookPhase,
+    constants, context, docker, health, hooks, network,
     prelude::*,
-    presentation,
+    presentation, readiness, releases,
     services::{self, ToContainerConfig},
 };
 
 const WATCH_POLL_INTERVAL: time::Duration = time::Duration::from_secs(1);
 const WATCH_COOLDOWN: time::Duration = time::Duration::from_secs(3);
 
+/// Suffix of the app container while it's being health checked
+pub(crate) const NEXT_CONTAINER_SUFFIX: &str = "_next";
+
 pub async fn deploy(
     context: &context::Context,
     docker: &bollard::Docker,
     services: &services::Services,
 ) -> Result<()> {
-    if dotenvy::from_path(context.app_config().env_file(context.override_context())).is_ok() {
-        presentation::print_env_file_loaded();
-    } else {
-        presentation::print_env_file_failed_to_load();
-    }
-
     if context.should_generate_env_file() {
         presentation::print_env_file_generating();
         generate_env(services, context)?;
@@ -41,24 +42,45 @@ pub async fn deploy(
         network::create_dploy_network(docker).await?;
     }
 
+    presentation::print_pre_up_running();
+    services.pre_up(context, docker).await?;
+
     presentation::print_dependencies_starting();
     deploy_dependencies(services, context, docker).await?;
 
-    if let Some(service) = services.app() {
-        deploy_app_service(service, context, docker).await?;
-    }
+    presentation::print_dependencies_readiness_checking();
+    wait_dependencies_ready(services, context, docker).await?;
+
+    deploy_app_service(services, context, docker).await?;
 
     presentation::print_post_up_running();
     services.post_up(docker).await?;
 
     if context.should_print_connection_info() {
-        let connection_info = services.connection_info();
+        let connection_info = services.connection_info(context);
         presentation::print_connection_info(&connection_info);
     }
 
     Ok(())
 }
 
+/// Loads the env file into the process environment.
+/// Must run before services are created, because they resolve env vars eagerly
+pub fn load_env_file(context: &context::Context) {
+    let is_loaded =
+        dotenvy::from_path(context.app_config().env_file(context.override_context())).is_ok();
+
+    if context.args().is_json_output() {
+        return;
+    }
+
+    if is_loaded {
+        presentation::print_env_file_loaded();
+    } else {
+        presentation::print_env_file_failed_to_load();
+    }
+}
+
 pub async fn deploy_watch(
     context: Arc<context::Context>,
     docker: Arc<bollard::Docker>,
@@ -73,7 +95,7 @@ pub async fn deploy_watch(
     let mut handle = tokio::spawn(commands::logs::logs(
         Arc::clone(&context),
         Arc::clone(&docker),
-        services::ServiceKind::App,
+        ServiceSelector::Kind(services::ServiceKind::App),
         None,
     ));
 
@@ -117,14 +139,12 @@ pub async fn deploy_watch(
 
             handle.abort();
 
-            if let Some(service) = services.app() {
-                deploy_app_service(service, &context, &docker).await?;
-            }
+            deploy_app_service(services, &context, &docker).await?;
 
             handle = tokio::spawn(commands::logs::logs(
                 Arc::clone(&context),
                 Arc::clone(&docker),
-                services::ServiceKind::App,
+                ServiceSelector::Kind(services::ServiceKind::App),
                 None,
             ));
 
@@ -140,37 +160,263 @@ pub async fn deploy_watch(
 }
 
 async fn deploy_app_service(
+    services: &services::Services,
+    context: &context::Context,
+    docker: &bollard::Docker,
+) -> Result<()> {
+    if services.apps().is_empty() {
+        return Ok(());
+    }
+
+    // all apps share the release, so they are rolled back together
+    let release = releases::new_release_id();
+
+    // apps sharing the dockerfile and the context are built once,
+    // the others are tagged with the same image
+    let mut built_images = HashMap::<(&str, &str), (&str, String)>::new();
+
+    for app_service in services.apps() {
+        let container_name = app_service.container_name();
+
+        if let Some(image) = app_service.prebuilt_image() {
+            presentation::print_image_pulling(container_name, image);
+            build::pull_app_service_image(app_service, docker, image, &release).await?;
+            presentation::print_image_pulled(container_name);
+
+            continue;
+        }
+
+        let source = (app_service.docker_context(), app_service.dockerfile());
+
+        if let Some((built_by, image_id)) = built_images.get(&source) {
+            build::tag_app_service_image(app_service, docker, image_id, &release).await?;
+            presentation::print_image_shared(container_name, built_by);
+
+            continue;
+        }
+
+        presentation::print_image_building(container_name, app_service.dockerfile());
+        let image_id =
+            build::build_app_service_image(context, app_service, docker, &release).await?;
+        presentation::print_image_built(container_name);
+
+        built_images.insert(source, (container_name, image_id));
+    }
+
+    hooks::run(context, docker, services, HookPhase::PreApp, &release).await?;
+
+    // images the apps ran before, restored if a later app fails to start
+    let mut started = vec![];
+
+    for app_service in services.apps() {
+        let previous_image_id =
+            releases::current_image_id(docker, app_service.image_name()).await?;
+
+        if let Err(error) =
+            start_app_service_release(app_service, services, context, docker, &release).await
+        {
+            // without blue/green the old container of the failed app is already gone
+            if !is_blue_green(app_service, context) {
+                started.push((app_service, previous_image_id));
+            }
+
+            if let Err(restore_error) =
+                restore_app_services(&started, services, context, docker).await
+            {
+                bail!("{error}\n\nRestoring the previous release failed as well: {restore_error}");
+            }
+
+            return Err(error);
+        }
+
+        started.push((app_service, previous_image_id));
+    }
+
+    hooks::run(context, docker, services, HookPhase::PostApp, &release).await?;
+
+    for app_service in services.apps() {
+        let removed = releases::prune(
+            docker,
+            app_service.image_name(),
+            context
+                .app_config()
+                .keep_releases(context.override_context()),
+        )
+        .await?;
+
+        for release in removed {
+            presentation::print_release_removed(&release);
+        }
+    }
+
+    Ok(())
+}
+
+/// Recreates the app containers from a previously built release without rebuilding.
+/// Without an explicit release the one before the current release of the top-level app is used
+pub async fn rollback(
+    context: &context::Context,
+    docker: &bollard::Docker,
+    services: &services::Services,
+    release: Option<&str>,
+) -> Result<()> {
+    let Some(app_service) = services.app() else {
+        return Ok(());
+    };
+
+    let image_name = app_service.image_name();
+    let available = releases::list(docker, image_name).await?;
+
+    let release = match release {
+        Some(release) => available
+            .iter()
+            .find(|available| available.id() == release)
+            .with_context(|| {
+                format!("Release {release} was not found. Run `releases` to list available ones")
+            })?,
+        None => {
+            let current_image_id = releases::current_image_id(docker, image_name).await?;
+
+            // releases are sorted newest first, so the previous one
+            // is the first with another image after the current one
+            let current_position = available
+                .iter()
+                .position(|release| Some(release.image_id()) == current_image_id.as_deref())
+                .unwrap_or(0);
+
+            available
+                .iter()
+                .skip(current_position)
+                .find(|release| Some(release.image_id()) != current_image_id.as_deref())
+                .context("There is no previous release to roll back to")?
+        }
+    };
+
+    // apps added after the release was built can't be rolled back to it
+    for app_service in services.apps() {
+        let is_available = releases::list(docker, app_service.image_name())
+            .await?
+            .iter()
+            .any(|available| available.id() == release.id());
+
+        if !is_available {
+            bail!(
+                "Release {} of {} was not found. Run `releases` to list available ones",
+                release.id(),
+                app_service.name()
+            );
+        }
+    }
+
+    presentation::print_release_rolling_back(release.id());
+
+    for app_service in services.apps() {
+        start_app_service_release(app_service, services, context, docker, release.id()).await?;
+    }
+
+    presentation::print_post_up_running();
+    services.post_up(docker).await?;
+
+    Ok(())
+}
+
+/// Points the apps which were already started back to the images they ran before.
+/// Apps deployed for the first time have nothing to go back to and keep running
+async fn restore_app_services(
+    started: &[(&services::app::AppService, Option<String>)],
+    services: &services::Services,
+    context: &context::Context,
+    docker: &bollard::Docker,
+) -> Result<()> {
+    for (app_service, previous_image_id) in started.iter().rev() {
+        let Some(previous_image_id) = previous_image_id else {
+            continue;
+        };
+
+        presentation::print_app_container_restoring(app_service.container_name());
+        start_app_service_image(app_service, services, context, docker, previous_image_id).await?;
+    }
+
+    Ok(())
+}
+
+async fn start_app_service_release(
     app_service: &services::app::AppService,
+    services: &services::Services,
+    context: &context::Context,
+    docker: &bollard::Docker,
+    release: &str,
+) -> Result<()> {
+    let image = format!("{}:{release}", app_service.image_name());
+
+    start_app_service_image(app_service, services, context, docker, &image).await
+}
+
+/// Points the current tag to the image, which is a reference or an id,
+/// and replaces the app container with one running it
+async fn start_app_service_image(
+    app_service: &services::app::AppService,
+    services: &services::Services,
     context: &context::Context,
     docker: &bollard::Docker,
+    image: &str,
 ) -> Result<()> {
     let container_config = app_service.to_container_config(context)?;
-    let container_name = container_config.container_name();
-    let dockerfile = context.app_config().dockerfile(context.override_context());
-
-    presentation::print_image_building(container_name, dockerfile);
-    build::build_app_service_image(context, app_service, docker).await?;
-    presentation::print_image_built(container_name);
-
-    let existing_container = match docker.inspect_container(container_name, None).await {
-        Ok(container) => Some(container),
-        Err(bollard::errors::Error::DockerResponseServerError {
-            status_code: 404, ..
-        }) => None,
-        Err(e) => return Err(e.into()),
+    let image_name = app_service.image_name();
+
+    let previous_image_id = releases::current_image_id(docker, image_name).await?;
+    releases::tag_current(docker, image_name, image).await?;
+
+    let result = if is_blue_green(app_service, context) {
+        switch_app_service(app_service, services, &container_config, docker).await
+    } else {
+        replace_app_service(app_service, &container_config, docker).await
     };
 
+    // keep the current tag in sync with the container which is still running
+    if let (Err(_), Some(previous_image_id)) = (&result, previous_image_id) {
+        releases::tag_current(docker, image_name, &previous_image_id).await?;
+    }
+
+    result
+}
+
+/// Apps without routes, like workers, don't need to overlap
+fn is_blue_green(app_service: &services::app::AppService, context: &context::Context) -> bool {
+    context.should_create_proxy_service() && !app_service.proxy().is_empty()
+}
+
+/// Stops the old container and starts the new one in its place
+async fn replace_app_service(
+    app_service: &services::app::AppService,
+    container_config: &services::ContainerConfig,
+    docker: &bollard::Docker,
+) -> Result<()> {
+    let container_name = container_config.container_name();
+
+    let existing_container = docker::inspect_container(docker, container_name).await?;
+
     if existing_container.is_some() {
         presentation::print_app_container_removing(container_name);
-        docker.stop_container(container_name, None).await?;
-        docker.remove_container(container_name, None).await?;
+        docker
+            .stop_container(
+                container_name,
+                None::<bollard::query_parameters::StopContainerOptions>,
+            )
+            .await?;
+        docker
+            .remove_container(
+                container_name,
+                None::<bollard::query_parameters::RemoveContainerOptions>,
+            )
+            .await?;
     }
 
     presentation::print_app_container_creating(container_name);
     docker
         .create_container(
-            Some(bollard::container::CreateContainerOptions {
-                name: container_name,
+            Some(bollard::query_parameters::CreateContainerOptions {
+                name: Some(container_name.to_owned()),
                 ..Default::default()
             }),
             container_config.config().clone(),
@@ -181,15 +427,198 @@ async fn deploy_app_service(
     docker
         .start_container(
             container_name,
-            None::<bollard::container::StartContainerOptions<String>>,
+            None::<bollard::query_parameters::StartContainerOptions>,
         )
         .await?;
 
+    wait_app_service_healthy(app_service, docker, container_name, container_name).await?;
+
     presentation::print_app_container_success(container_name);
 
     Ok(())
 }
 
+/// Blue/green deploy: the new container is started next to the old one
+/// under a temporary name and the proxy is switched to it once it's healthy.
+/// The old container is left untouched if anything fails before the switch.
+/// After the switch the new container serves traffic even under the temporary name,
+/// so if renaming it fails, the next deploy takes it over instead of removing it
+async fn switch_app_service(
+    app_service: &services::app::AppService,
+    services: &services::Services,
+    container_config: &services::ContainerConfig,
+    docker: &bollard::Docker,
+) -> Result<()> {
+    let container_name = container_config.container_name();
+    let next_container_name = format!("{container_name}{NEXT_CONTAINER_SUFFIX}");
+
+    // unique alias, so the proxy can reach the new container before and after the rename
+    let release = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)?
+        .as_millis();
+    let upstream = format!("{container_name}_{release}");
+
+    recover_next_container(services, docker, container_name, &next_container_name).await?;
+    docker::remove_container_if_exists(docker, &next_container_name).await?;
+
+    presentation::print_app_container_creating(&next_container_name);
+    docker
+        .create_container(
+            Some(bollard::query_parameters::CreateContainerOptions {
+                name: Some(next_container_name.as_str().to_owned()),
+                ..Default::default()
+            }),
+            with_upstream_alias(container_config.config(), &upstream),
+        )
+        .await?;
+
+    presentation::print_app_container_starting(&next_container_name);
+    let started = async {
+        docker
+            .start_container(
+                &next_container_name,
+                None::<bollard::query_parameters::StartContainerOptions>,
+            )
+            .await?;
+
+        wait_app_service_healthy(app_service, docker, &next_container_name, &upstream).await?;
+
+        presentation::print_proxy_updating(services.proxy().name());
+        services
+            .proxy()
+            .switch_upstream(docker, container_name, &upstream)
+            .await?;
+
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    if let Err(error) = started {
+        presentation::print_app_container_removing(&next_container_name);
+        docker::remove_container_if_exists(docker, &next_container_name).await?;
+
+        return Err(error);
+    }
+
+    replace_app_container(docker, container_name, &next_container_name).await?;
+
+    presentation::print_app_container_success(container_name);
+
+    Ok(())
+}
+
+/// A next container left over from an earlier deploy is renamed into place
+/// if the proxy routes to it. Otherwise it was left by a failed attempt and gets replaced
+async fn recover_next_container(
+    services: &services::Services,
+    docker: &bollard::Docker,
+    container_name: &str,
+    next_container_name: &str,
+) -> Result<()> {
+    let Some(upstream) = docker::inspect_container(docker, next_container_name)
+        .await?
+        .and_then(|container| container.config)
+        .and_then(|config| config.labels)
+        .and_then(|mut labels| labels.remove(services::UPSTREAM_LABEL))
+    else {
+        return Ok(());
+    };
+
+    if !services
+        .proxy()
+        .is_routed_to(docker, container_name, &upstream)
+        .await?
+    {
+        return Ok(());
+    }
+
+    presentation::print_app_container_recovering(next_container_name);
+    replace_app_container(docker, container_name, next_container_name).await
+}
+
+/// Stops and removes the old container and gives its name to the next one
+async fn replace_app_container(
+    docker: &bollard::Docker,
+    container_name: &str,
+    next_container_name: &str,
+) -> Result<()> {
+    if docker::inspect_container(docker, container_name)
+        .await?
+        .is_some()
+    {
+        presentation::print_app_container_removing(container_name);
+        docker
+            .stop_container(
+                container_name,
+                None::<bollard::query_parameters::StopContainerOptions>,
+            )
+            .await?;
+        docker
+            .remove_container(
+                container_name,
+                None::<bollard::query_parameters::RemoveContainerOptions>,
+            )
+            .await?;
+    }
+
+    docker
+        .rename_container(
+            next_container_name,
+            bollard::query_parameters::RenameContainerOptions {
+                name: container_name.to_owned(),
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Waits for the docker healthcheck if it's configured,
+/// otherwise for the default probe if there is one
+async fn wait_app_service_healthy(
+    app_service: &services::app::AppService,
+    docker: &bollard::Docker,
+    container_name: &str,
+    host: &str,
+) -> Result<()> {
+    if let Some(healthcheck) = app_service.healthcheck() {
+        presentation::print_app_container_health_checking(container_name);
+        health::wait_container_healthy(docker, container_name, healthcheck.timeout()).await?;
+    } else if let Some(probe) = app_service.default_probe() {
+        presentation::print_app_container_health_checking(container_name);
+        health::wait_probe(docker, container_name, host, probe).await?;
+    }
+
+    Ok(())
+}
+
+fn with_upstream_alias(
+    config: &bollard::models::ContainerCreateBody,
+    upstream: &str,
+) -> bollard::models::ContainerCreateBody {
+    let mut config = config.clone();
+
+    config
+        .labels
+        .get_or_insert_with(Default::default)
+        .insert(services::UPSTREAM_LABEL.to_owned(), upstream.to_owned());
+
+    if let Some(endpoints_config) = config
+        .networking_config
+        .as_mut()
+        .and_then(|networking_config| networking_config.endpoints_config.as_mut())
+    {
+        for endpoint in endpoints_config.values_mut() {
+            endpoint
+                .aliases
+                .get_or_insert_with(Default::default)
+                .push(upstream.to_owned());
+        }
+    }
+
+    config
+}
+
 fn generate_env(services: &services::Services, context: &context::Context) -> Result<()> {
     let existing_env = get_existing_env(context.app_config().env_file(context.override_context()));
     let is_generated_first_time = existing_env.is_none();
@@ -202,6 +631,10 @@ fn generate_env(services: &services::Services, context: &context::Context) -> Re
         own_env_vars_names.insert(env_name.clone());
     }
 
+    for app in context.app_config().apps(context.override_context()) {
+        own_env_vars_names.extend(app.env().iter().cloned());
+    }
+
     for env_name in existing_env.keys() {
         own_env_vars_names.insert(env_name.clone());
     }
@@ -285,18 +718,17 @@ async fn deploy_dependencies(
 ) -> Result<()> {
     let container_configs = services.to_container_configs(context)?;
 
-    for config in container_configs {
+    for config in &container_configs {
         let container_name = config.container_name();
         let image_name = config.image_name();
-        let config = config.config();
+        let image_tag = config.image_tag();
 
         presentation::print_dependency_pulling(container_name);
         docker
             .create_image(
-                Some(bollard::image::CreateImageOptions {
-                    from_image: image_name,
-                    // TODO: allow users to set tag
-                    tag: "latest",
+                Some(bollard::query_parameters::CreateImageOptions {
+                    from_image: Some(image_name.to_owned()),
+                    tag: Some(image_tag.to_owned()),
                     ..Default::default()
                 }),
                 None,
@@ -305,39 +737,113 @@ async fn deploy_dependencies(
             .try_collect::<Vec<_>>()
             .await?;
 
-        // TODO: check here if container exists and version is the same
+        let image_id = docker
+            .inspect_image(&config.image())
+            .await?
+            .id
+            .unwrap_or_default();
+        let fingerprint = config.fingerprint(&image_id)?;
+
         let existing_container = docker::inspect_container(docker, container_name).await?;
 
-        presentation::print_dependency_creating(container_name);
+        let is_up_to_date = existing_container
+            .as_ref()
+            .and_then(|container| container.config.as_ref())
+            .and_then(|config| config.labels.as_ref())
+            .and_then(|labels| labels.get(services::FINGERPRINT_LABEL))
+            .is_some_and(|existing_fingerprint| existing_fingerprint == &fingerprint);
 
-        if existing_container.is_some() {
+        if is_up_to_date {
             if docker::check_container_running(docker, container_name).await? {
-                docker.stop_container(container_name, None).await?;
+                presentation::print_dependency_up_to_date(container_name);
+                continue;
+            }
+        } else {
+            presentation::print_dependency_creating(container_name);
+
+            if existing_container.is_some() {
+                if docker::check_container_running(docker, container_name).await? {
+                    docker
+                        .stop_container(
+                            container_name,
+                            None::<bollard::query_parameters::StopContainerOptions>,
+                        )
+                        .await?;
+                }
+
+                docker
+                    .remove_container(
+                        container_name,
+                        None::<bollard::query_parameters::RemoveContainerOptions>,
+                    )
+                    .await?;
             }
 
-            docker.remove_container(container_name, None).await?;
+            docker
+                .create_container(
+                    Some(bollard::query_parameters::CreateContainerOptions {
+                        name: Some(container_name.to_owned()),
+                        ..Default::default()
+                    }),
+                    config.config_with_fingerprint(&fingerprint),
+                )
+                .await?;
         }
 
-        docker
-            .create_container(
-                Some(bollard::container::CreateContainerOptions {
-                    name: container_name,
-                    ..Default::default()
-                }),
-                config.clone(),
+        for file in config.files() {
+            docker::upload_file(
+                docker,
+                container_name,
+                &file.dir,
+                &file.name,
+                file.contents.as_bytes(),
             )
             .await?;
+        }
 
         presentation::print_dependency_starting(container_name);
         docker
             .start_container(
                 container_name,
-                None::<bollard::container::StartContainerOptions<String>>,
+                None::<bollard::query_parameters::StartContainerOptions>,
             )
             .await?;
 
         presentation::print_dependency_success(container_name);
     }
 
+    for config in &container_configs {
+        if config.config().healthcheck.is_none() {
+            continue;
+        }
+
+        let container_name = config.container_name();
+
+        presentation::print_dependency_health_checking(container_name);
+        health::wait_container_healthy(
+            docker,
+            container_name,
+            time::Duration::from_secs(constants::DEFAULT_HEALTHCHECK_TIMEOUT),
+        )
+        .await?;
+        presentation::print_dependency_healthy(container_name);
+    }
+
+    Ok(())
+}
+
+/// Runs after the docker health checks passed, which only cover the
+/// container itself, to make sure the dependencies accept connections
+async fn wait_dependencies_ready(
+    services: &services::Services,
+    context: &context::Context,
+    docker: &bollard::Docker,
+) -> Result<()> {
+    let timeout = time::Duration::from_secs(constants::DEFAULT_HEALTHCHECK_TIMEOUT);
+
+    for readiness in services.to_readiness(context) {
+        readiness::wait_ready(docker, &readiness, timeout).await?;
+    }
+
     Ok(())
 }
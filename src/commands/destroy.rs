@@ -0,0 +1,159 @@
+use std::io::{self, BufRead, IsTerminal};
+
+use crate::{
+    commands::deploy::NEXT_CONTAINER_SUFFIX,
+    context, docker, helper, network,
+    prelude::*,
+    presentation, releases,
+    services::{proxy::ProxyService, ServiceKind},
+};
+
+/// Dependencies which may have persisted data in the namespace, next to the apps
+const VOLUME_SERVICE_KINDS: &[ServiceKind] = &[ServiceKind::Postgres, ServiceKind::Keydb];
+
+#[derive(Debug, Clone)]
+pub struct DestroyArgs {
+    volumes: bool,
+    yes: bool,
+}
+
+impl DestroyArgs {
+    pub fn new(volumes: bool, yes: bool) -> Self {
+        Self { volumes, yes }
+    }
+
+    pub fn volumes(&self) -> bool {
+        self.volumes
+    }
+
+    pub fn yes(&self) -> bool {
+        self.yes
+    }
+}
+
+pub async fn destroy(
+    context: &context::Context,
+    docker: &bollard::Docker,
+    args: &DestroyArgs,
+) -> Result<()> {
+    let volume_container_names = volume_container_names(context);
+
+    if args.volumes() && !args.yes() {
+        let volumes_dirs = volume_container_names
+            .iter()
+            .map(|container_name| context.volumes_dir_of_container(container_name))
+            .collect::<Vec<_>>();
+
+        confirm_volumes_removal(&volumes_dirs)?;
+    }
+
+    remove_containers(context, docker).await?;
+
+    presentation::print_post_down_running();
+    ProxyService::from_context(context, docker)
+        .await?
+        .post_down(docker)
+        .await?;
+
+    // app images are named after the app containers
+    for image_name in context.app_container_names() {
+        for reference in releases::remove_all(docker, &image_name).await? {
+            presentation::print_destroy_removed("image", &reference);
+        }
+    }
+
+    if network::remove_dploy_network_if_unused(docker).await? {
+        presentation::print_destroy_removed("network", network::DPLOY_NETWORK);
+    }
+
+    if args.volumes() {
+        for container_name in volume_container_names {
+            if helper::remove_volumes_dir(docker, context, &container_name).await? {
+                presentation::print_destroy_removed(
+                    "volume",
+                    &context
+                        .volumes_dir_of_container(&container_name)
+                        .to_string_lossy(),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Containers whose volumes belong to the namespace
+fn volume_container_names(context: &context::Context) -> Vec<String> {
+    let mut service_kinds = VOLUME_SERVICE_KINDS.to_vec();
+
+    // the singleton proxy is shared, only the local one belongs to the namespace
+    if context.is_local_proxy() {
+        service_kinds.push(ServiceKind::Proxy);
+    }
+
+    context
+        .app_container_names()
+        .into_iter()
+        .chain(
+            service_kinds
+                .into_iter()
+                .map(|service_kind| context.container_name_of(service_kind)),
+        )
+        .collect()
+}
+
+fn confirm_volumes_removal(volumes_dirs: &[std::path::PathBuf]) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        bail!("Deleting volumes requires confirmation. Pass --yes to confirm");
+    }
+
+    presentation::print_destroy_volumes_confirmation(volumes_dirs);
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        bail!("Aborted, nothing was removed");
+    }
+
+    Ok(())
+}
+
+async fn remove_containers(context: &context::Context, docker: &bollard::Docker) -> Result<()> {
+    let mut container_names = vec![];
+
+    for app_container_name in context.app_container_names() {
+        container_names.extend([
+            app_container_name.clone(),
+            // left over from an interrupted blue/green deploy
+            format!("{app_container_name}{NEXT_CONTAINER_SUFFIX}"),
+        ]);
+    }
+
+    let mut service_kinds = context.dependency_kinds();
+
+    // the singleton proxy is shared between projects, so only the local one is removed
+    if context.is_local_proxy() && context.app_config().has_proxy(context.override_context()) {
+        service_kinds.push(ServiceKind::Proxy);
+    }
+
+    container_names.extend(
+        service_kinds
+            .into_iter()
+            .map(|service_kind| context.container_name_of(service_kind)),
+    );
+
+    for container_name in container_names {
+        if docker::inspect_container(docker, &container_name)
+            .await?
+            .is_none()
+        {
+            continue;
+        }
+
+        docker::remove_container_if_exists(docker, &container_name).await?;
+        presentation::print_destroy_removed("container", &container_name);
+    }
+
+    Ok(())
+}
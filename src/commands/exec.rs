@@ -1,18 +1,18 @@
-use crate::{context, docker, prelude::*, presentation, services};
+use crate::{cli::ServiceSelector, context, docker, prelude::*, presentation};
 
 #[derive(Debug, Clone)]
 pub struct ExecArgs {
-    service: services::ServiceKind,
+    service: ServiceSelector,
     command: String,
 }
 
 impl ExecArgs {
-    pub fn new(service: services::ServiceKind, command: String) -> Self {
+    pub fn new(service: ServiceSelector, command: String) -> Self {
         Self { service, command }
     }
 
-    pub fn service(&self) -> services::ServiceKind {
-        self.service
+    pub fn service(&self) -> &ServiceSelector {
+        &self.service
     }
 
     pub fn command(&self) -> &str {
@@ -20,13 +20,13 @@ impl ExecArgs {
     }
 }
 
+/// Returns the exit code of the command
 pub async fn exec(
     context: &context::Context,
     docker: &bollard::Docker,
     args: &ExecArgs,
-) -> Result<()> {
-    let service_kind = args.service();
-    let container_name = context.container_name_of(service_kind);
+) -> Result<i64> {
+    let container_name = context.container_name_of_selected(args.service())?;
 
     let is_running = docker::check_container_running(docker, &container_name).await?;
     if !is_running {
@@ -36,7 +36,7 @@ pub async fn exec(
     let command = args.command();
 
     presentation::print_command_executing(&container_name);
-    docker::exec_command_attached(docker, &container_name, command).await?;
+    let output = docker::exec_command_attached(docker, &container_name, command).await?;
 
-    Ok(())
+    Ok(output.exit_code)
 }
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use crate::{
+    commands::ls::{self, ListedNamespace},
+    context, docker,
+    prelude::*,
+    presentation, releases,
+    services::ServiceKind,
+};
+
+#[derive(Debug, Clone)]
+pub struct GcArgs {
+    stale: Option<Duration>,
+    dry_run: bool,
+}
+
+impl GcArgs {
+    pub fn new(stale: Option<Duration>, dry_run: bool) -> Self {
+        Self { stale, dry_run }
+    }
+
+    /// Namespaces stopped for longer than this are removed
+    pub fn stale(&self) -> Option<Duration> {
+        self.stale
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+pub async fn gc(docker: &bollard::Docker, args: &GcArgs) -> Result<()> {
+    let mut is_clean = true;
+
+    for image_id in releases::remove_dangling(docker, args.dry_run()).await? {
+        presentation::print_gc_removed("image", &image_id, args.dry_run());
+        is_clean = false;
+    }
+
+    if let Some(stale) = args.stale() {
+        for namespace in ls::list_namespaces(docker).await? {
+            // the shared proxy is removed by the last project using it
+            if namespace.project() == context::SINGLETON_PROJECT {
+                continue;
+            }
+
+            let Some(stopped_for) = stopped_for(docker, &namespace).await? else {
+                continue;
+            };
+
+            if stopped_for < stale {
+                continue;
+            }
+
+            presentation::print_gc_namespace_stale(
+                namespace.project(),
+                namespace.namespace(),
+                stopped_for,
+            );
+            remove_namespace(docker, &namespace, args.dry_run()).await?;
+            is_clean = false;
+        }
+    }
+
+    if is_clean {
+        presentation::print_gc_nothing_to_remove();
+    }
+
+    Ok(())
+}
+
+/// Time since the last container of the namespace stopped,
+/// `None` while any of them is running
+async fn stopped_for(
+    docker: &bollard::Docker,
+    namespace: &ListedNamespace,
+) -> Result<Option<Duration>> {
+    let mut last_stopped_at = None;
+
+    for container in namespace.containers() {
+        let Some(inspected) = docker::inspect_container(docker, container.name()).await? else {
+            continue;
+        };
+
+        let state = inspected.state.unwrap_or_default();
+
+        if state.running.unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let parse = |time: Option<String>| {
+            time.and_then(|time| chrono::DateTime::parse_from_rfc3339(&time).ok())
+                .map(|time| time.to_utc())
+        };
+
+        // containers which were never started have no valid finish time
+        let stopped_at = parse(state.finished_at)
+            .into_iter()
+            .chain(inspected.created)
+            .max();
+
+        last_stopped_at = last_stopped_at.max(stopped_at);
+    }
+
+    Ok(last_stopped_at.map(|stopped_at| {
+        chrono::Utc::now()
+            .signed_duration_since(stopped_at)
+            .to_std()
+            .unwrap_or_default()
+    }))
+}
+
+/// Removes the containers and the app images of the namespace, volumes are kept
+async fn remove_namespace(
+    docker: &bollard::Docker,
+    namespace: &ListedNamespace,
+    dry_run: bool,
+) -> Result<()> {
+    for container in namespace.containers() {
+        if !dry_run {
+            docker::remove_container_if_exists(docker, container.name()).await?;
+        }
+
+        presentation::print_gc_removed("container", container.name(), dry_run);
+    }
+
+    // the app image is named after the app container
+    let app_image_names = namespace
+        .containers()
+        .iter()
+        .filter(|container| container.kind() == ServiceKind::App.to_string())
+        .map(|container| container.name());
+
+    for image_name in app_image_names {
+        if dry_run {
+            for release in releases::list(docker, image_name).await? {
+                presentation::print_gc_removed(
+                    "image",
+                    &format!("{image_name}:{}", release.id()),
+                    dry_run,
+                );
+            }
+
+            continue;
+        }
+
+        for reference in releases::remove_all(docker, image_name).await? {
+            presentation::print_gc_removed("image", &reference, dry_run);
+        }
+    }
+
+    Ok(())
+}
@@ -2,26 +2,26 @@ use std::{io::Write, sync::Arc};
 
 use futures_util::TryStreamExt;
 
-use crate::{context, docker, prelude::*, presentation, services};
+use crate::{cli::ServiceSelector, context, docker, prelude::*, presentation};
 
 pub async fn logs(
     context: Arc<context::Context>,
     docker: Arc<bollard::Docker>,
-    service: services::ServiceKind,
+    service: ServiceSelector,
     count: Option<u64>,
 ) -> Result<()> {
     let logs_count = count.unwrap_or(20);
     let should_follow = count.is_none();
-    let container_name = context.container_name_of(service);
+    let container_name = context.container_name_of_selected(&service)?;
 
-    let is_running = docker::check_container_running(&*docker, &container_name).await?;
+    let is_running = docker::check_container_running(&docker, &container_name).await?;
     if !is_running {
         bail!("Cannot show logs because the container is not running. Deploy it first.");
     }
 
     let logs = docker.logs(
         &container_name,
-        Some(bollard::container::LogsOptions {
+        Some(bollard::query_parameters::LogsOptions {
             stdout: true,
             stderr: true,
             follow: should_follow,
@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{helper, prelude::*, presentation, services};
+
+#[derive(Debug, Clone)]
+pub struct ListedNamespace {
+    project: String,
+    namespace: String,
+    containers: Vec<ListedContainer>,
+
+    /// Bytes used by the volumes of the containers, only filled in by `ls`
+    volumes_size: u64,
+}
+
+impl ListedNamespace {
+    pub fn project(&self) -> &str {
+        &self.project
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn containers(&self) -> &[ListedContainer] {
+        &self.containers
+    }
+
+    pub fn volumes_size(&self) -> u64 {
+        self.volumes_size
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ListedContainer {
+    name: String,
+    kind: String,
+    state: String,
+
+    /// Human readable status reported by docker, like `Up 2 hours`
+    status: String,
+
+    /// Version of dploy the container was created with
+    version: String,
+}
+
+impl ListedContainer {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+}
+
+pub async fn ls(docker: &bollard::Docker) -> Result<()> {
+    let mut namespaces = list_namespaces(docker).await?;
+
+    if !namespaces.is_empty() {
+        let volumes_sizes = helper::volumes_disk_usage(docker).await?;
+
+        for namespace in &mut namespaces {
+            namespace.volumes_size = namespace
+                .containers
+                .iter()
+                .filter_map(|container| volumes_sizes.get(&container.name))
+                .sum();
+        }
+    }
+
+    presentation::print_namespaces(&namespaces);
+
+    Ok(())
+}
+
+/// Containers created by dploy on the host, grouped by project and namespace
+pub async fn list_namespaces(docker: &bollard::Docker) -> Result<Vec<ListedNamespace>> {
+    let containers = docker
+        .list_containers(Some(bollard::query_parameters::ListContainersOptions {
+            all: true,
+            filters: Some(HashMap::from([(
+                "label".to_owned(),
+                vec![services::PROJECT_LABEL.to_owned()],
+            )])),
+            ..Default::default()
+        }))
+        .await?;
+
+    let mut namespaces = BTreeMap::<(String, String), Vec<ListedContainer>>::new();
+
+    for container in containers {
+        let mut labels = container.labels.unwrap_or_default();
+        let mut label = |key: &str| labels.remove(key).unwrap_or_default();
+
+        let project = label(services::PROJECT_LABEL);
+        let namespace = label(services::NAMESPACE_LABEL);
+
+        let listed = ListedContainer {
+            name: container
+                .names
+                .and_then(|names| names.into_iter().next())
+                .map(|name| name.trim_start_matches('/').to_owned())
+                .unwrap_or_default(),
+            kind: label(services::KIND_LABEL),
+            version: label(services::VERSION_LABEL),
+            state: container
+                .state
+                .map(|state| state.to_string())
+                .unwrap_or_default(),
+            status: container.status.unwrap_or_default(),
+        };
+
+        namespaces
+            .entry((project, namespace))
+            .or_default()
+            .push(listed);
+    }
+
+    Ok(namespaces
+        .into_iter()
+        .map(|((project, namespace), mut containers)| {
+            containers.sort_by(|a, b| a.name.cmp(&b.name));
+
+            ListedNamespace {
+                project,
+                namespace,
+                containers,
+                volumes_size: 0,
+            }
+        })
+        .collect())
+}
@@ -1,4 +1,10 @@
 pub mod deploy;
+pub mod destroy;
 pub mod exec;
+pub mod gc;
 pub mod logs;
+pub mod ls;
+pub mod proxy;
+pub mod releases;
+pub mod status;
 pub mod stop;
@@ -0,0 +1,66 @@
+use crate::{context, docker, prelude::*, presentation, services::proxy::ProxyService};
+
+pub async fn status(context: &context::Context, docker: &bollard::Docker) -> Result<()> {
+    let proxy = ProxyService::from_context(context, docker).await?;
+
+    let container = docker::inspect_container(docker, proxy.name()).await?;
+
+    let state = container
+        .as_ref()
+        .and_then(|container| container.state.as_ref())
+        .and_then(|state| state.status)
+        .map(|status| status.to_string());
+
+    let image = container
+        .as_ref()
+        .and_then(|container| container.config.as_ref())
+        .and_then(|config| config.image.clone());
+
+    let site_files = if docker::check_container_running(docker, proxy.name()).await? {
+        proxy.site_files(docker).await?
+    } else {
+        vec![]
+    };
+
+    presentation::print_proxy_status(
+        proxy.name(),
+        state.as_deref(),
+        image.as_deref(),
+        &site_files,
+    );
+
+    Ok(())
+}
+
+pub async fn restart(context: &context::Context, docker: &bollard::Docker) -> Result<()> {
+    let proxy = ProxyService::from_context(context, docker).await?;
+
+    if docker::inspect_container(docker, proxy.name())
+        .await?
+        .is_none()
+    {
+        bail!("{} does not exist", proxy.name());
+    }
+
+    presentation::print_proxy_restarting(proxy.name());
+    proxy.restart(docker).await?;
+    presentation::print_proxy_restarted(proxy.name());
+
+    Ok(())
+}
+
+pub async fn remove(context: &context::Context, docker: &bollard::Docker) -> Result<()> {
+    let proxy = ProxyService::from_context(context, docker).await?;
+
+    if docker::check_container_running(docker, proxy.name()).await? {
+        for site_file in proxy.site_files(docker).await? {
+            presentation::print_proxy_site_unrouted(&site_file);
+        }
+    }
+
+    presentation::print_proxy_removing(proxy.name());
+    proxy.remove(docker).await?;
+    presentation::print_proxy_removed(proxy.name());
+
+    Ok(())
+}
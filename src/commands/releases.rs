@@ -0,0 +1,13 @@
+use crate::{context, prelude::*, presentation, releases};
+
+pub async fn releases(context: &context::Context, docker: &bollard::Docker) -> Result<()> {
+    // app images are named after the app containers
+    for image_name in context.app_container_names() {
+        let available = releases::list(docker, &image_name).await?;
+        let current_image_id = releases::current_image_id(docker, &image_name).await?;
+
+        presentation::print_releases(&image_name, &available, current_image_id.as_deref());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+
+use crate::{context, docker, prelude::*, presentation, releases};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    container: String,
+
+    /// `None` if the container does not exist
+    state: Option<String>,
+    health: Option<String>,
+
+    /// Seconds since the container was started, only while it's running
+    uptime: Option<i64>,
+
+    image_id: Option<String>,
+    release: Option<String>,
+    restart_count: Option<i64>,
+
+    /// Bindings like `127.0.0.1:5432 -> 5432/tcp`
+    ports: Vec<String>,
+
+    /// Domains routed to the container by the proxy
+    domains: Vec<String>,
+}
+
+impl ServiceStatus {
+    pub fn container(&self) -> &str {
+        &self.container
+    }
+
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    pub fn health(&self) -> Option<&str> {
+        self.health.as_deref()
+    }
+
+    pub fn uptime(&self) -> Option<i64> {
+        self.uptime
+    }
+
+    pub fn image_id(&self) -> Option<&str> {
+        self.image_id.as_deref()
+    }
+
+    pub fn release(&self) -> Option<&str> {
+        self.release.as_deref()
+    }
+
+    pub fn restart_count(&self) -> Option<i64> {
+        self.restart_count
+    }
+
+    pub fn ports(&self) -> &[String] {
+        &self.ports
+    }
+
+    pub fn domains(&self) -> &[String] {
+        &self.domains
+    }
+}
+
+/// Only inspects containers, so it never creates secrets or other state
+pub async fn status(
+    context: &context::Context,
+    docker: &bollard::Docker,
+    json: bool,
+) -> Result<()> {
+    let mut statuses = vec![];
+
+    if context.should_create_app_service() {
+        for (container_name, proxy) in context.app_routes() {
+            let mut status = inspect_status(docker, &container_name).await?;
+
+            status.domains = proxy.iter().map(|proxy| proxy.domain.clone()).collect();
+
+            statuses.push(status);
+        }
+    }
+
+    for service_kind in context.dependency_kinds() {
+        let container_name = context.container_name_of(service_kind);
+        statuses.push(inspect_status(docker, &container_name).await?);
+    }
+
+    if json {
+        presentation::print_status_json(&serde_json::to_string_pretty(&statuses)?);
+    } else {
+        presentation::print_status(&statuses);
+    }
+
+    Ok(())
+}
+
+async fn inspect_status(docker: &bollard::Docker, container_name: &str) -> Result<ServiceStatus> {
+    let mut status = ServiceStatus {
+        container: container_name.to_owned(),
+        state: None,
+        health: None,
+        uptime: None,
+        image_id: None,
+        release: None,
+        restart_count: None,
+        ports: vec![],
+        domains: vec![],
+    };
+
+    let Some(container) = docker::inspect_container(docker, container_name).await? else {
+        return Ok(status);
+    };
+
+    if let Some(state) = &container.state {
+        status.state = state.status.map(|status| status.to_string());
+
+        status.health = state
+            .health
+            .as_ref()
+            .and_then(|health| health.status)
+            .map(|status| status.to_string());
+
+        if state.running.unwrap_or(false) {
+            status.uptime = state
+                .started_at
+                .as_deref()
+                .and_then(|started_at| chrono::DateTime::parse_from_rfc3339(started_at).ok())
+                .map(|started_at| {
+                    chrono::Utc::now()
+                        .signed_duration_since(started_at)
+                        .num_seconds()
+                });
+        }
+    }
+
+    status.restart_count = container.restart_count;
+
+    if let Some(image_id) = &container.image {
+        status.release = docker
+            .inspect_image(image_id)
+            .await
+            .ok()
+            .and_then(|image| image.config)
+            .and_then(|config| config.labels)
+            .and_then(|mut labels| labels.remove(releases::RELEASE_LABEL));
+    }
+
+    status.image_id = container.image;
+
+    status.ports = container
+        .network_settings
+        .and_then(|network_settings| network_settings.ports)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(container_port, bindings)| {
+            bindings
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |binding| {
+                    format!(
+                        "{}:{} -> {container_port}",
+                        binding.host_ip.unwrap_or_default(),
+                        binding.host_port.unwrap_or_default()
+                    )
+                })
+        })
+        .collect();
+    status.ports.sort();
+
+    Ok(status)
+}
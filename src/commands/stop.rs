@@ -1,16 +1,42 @@
 use crate::{
-    context,
+    cli::ServiceSelector,
+    config::HookPhase,
+    context, hooks,
     prelude::*,
     presentation,
+    releases::CURRENT_TAG,
     services::{self, ToContainerConfig},
 };
 
+#[derive(Debug, Clone, Default)]
+pub struct StopArgs {
+    service: Option<ServiceSelector>,
+}
+
+impl StopArgs {
+    pub fn new(service: Option<ServiceSelector>) -> Self {
+        Self { service }
+    }
+
+    /// Single app to stop, everything is stopped without it
+    pub fn service(&self) -> Option<&ServiceSelector> {
+        self.service.as_ref()
+    }
+}
+
 pub async fn stop(
     context: &context::Context,
     docker: &bollard::Docker,
     services: &services::Services,
+    args: &StopArgs,
 ) -> Result<()> {
-    if let Some(service) = services.app() {
+    if let Some(service) = args.service() {
+        return stop_single_app_service(context, docker, services, service).await;
+    }
+
+    hooks::run(context, docker, services, HookPhase::PreStop, CURRENT_TAG).await?;
+
+    for service in services.apps() {
         stop_app_service(service, context, docker).await?;
     }
 
@@ -23,6 +49,34 @@ pub async fn stop(
     Ok(())
 }
 
+/// Stops the app and removes its routes, the dependencies keep running
+async fn stop_single_app_service(
+    context: &context::Context,
+    docker: &bollard::Docker,
+    services: &services::Services,
+    service: &ServiceSelector,
+) -> Result<()> {
+    let container_name = context.container_name_of_selected(service)?;
+
+    let Some(app_service) = services
+        .apps()
+        .iter()
+        .find(|app_service| app_service.container_name() == container_name)
+    else {
+        bail!("Only apps can be stopped separately, {container_name} is not an app");
+    };
+
+    stop_app_service(app_service, context, docker).await?;
+
+    presentation::print_post_down_running();
+    services
+        .proxy()
+        .post_down_app(docker, app_service.container_name())
+        .await?;
+
+    Ok(())
+}
+
 async fn stop_app_service(
     app_service: &services::app::AppService,
     context: &context::Context,
@@ -33,7 +87,13 @@ async fn stop_app_service(
 
     presentation::print_app_container_removing(container_name);
 
-    let existing_container = match docker.inspect_container(container_name, None).await {
+    let existing_container = match docker
+        .inspect_container(
+            container_name,
+            None::<bollard::query_parameters::InspectContainerOptions>,
+        )
+        .await
+    {
         Ok(container) => Some(container),
         Err(bollard::errors::Error::DockerResponseServerError {
             status_code: 404, ..
@@ -42,7 +102,12 @@ async fn stop_app_service(
     };
 
     if should_stop_container(existing_container.as_ref()) {
-        docker.stop_container(container_name, None).await?;
+        docker
+            .stop_container(
+                container_name,
+                None::<bollard::query_parameters::StopContainerOptions>,
+            )
+            .await?;
         presentation::print_app_container_stopped(container_name);
     } else {
         presentation::print_app_container_already_stopped(container_name);
@@ -61,7 +126,13 @@ async fn stop_dependencies(
     for config in container_configs {
         let container_name = config.container_name();
 
-        let existing_container = match docker.inspect_container(container_name, None).await {
+        let existing_container = match docker
+            .inspect_container(
+                container_name,
+                None::<bollard::query_parameters::InspectContainerOptions>,
+            )
+            .await
+        {
             Ok(container) => Some(container),
             Err(bollard::errors::Error::DockerResponseServerError {
                 status_code: 404, ..
@@ -71,7 +142,12 @@ async fn stop_dependencies(
 
         presentation::print_dependency_stopping(container_name);
         if should_stop_container(existing_container.as_ref()) {
-            docker.stop_container(container_name, None).await?;
+            docker
+                .stop_container(
+                    container_name,
+                    None::<bollard::query_parameters::StopContainerOptions>,
+                )
+                .await?;
             presentation::print_dependency_stopped(container_name);
         } else {
             presentation::print_dependency_already_stopped(container_name);
@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, fmt};
+
 use serde::Deserialize;
 
 use crate::constants;
@@ -45,6 +47,17 @@ pub enum OverrideRuleCommand {
     Deploy,
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildLocation {
+    /// Image is built by the docker daemon of the server
+    #[default]
+    Remote,
+
+    /// Image is built by the local docker daemon and sent to the server
+    Local,
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct TopLevelAppConfig {
     /// Name of the user's application
@@ -58,10 +71,40 @@ pub struct TopLevelAppConfig {
     #[serde(default = "constants::get_default_docker_context")]
     context: String,
 
+    /// Prebuilt image of the app, e.g. `registry.example.com/app:1.2.3`.
+    /// When set, the image is pulled instead of being built
+    #[serde(default)]
+    image: Option<String>,
+
+    /// Where and how the app image is built.
+    /// `build = "local"` is a shorthand for `[build] location = "local"`
+    #[serde(default, deserialize_with = "deserialize_build")]
+    build: BuildConfig,
+
+    /// Compress the locally built image before sending it to the server
+    #[serde(default = "constants::get_default_compress_image")]
+    compress_image: bool,
+
     /// Paths to .dockerignore files
     #[serde(default = "constants::get_default_ignore_files")]
     ignore_files: Vec<String>,
 
+    /// Command of the app container, replacing the `CMD` of the image
+    #[serde(default)]
+    command: Option<Vec<String>>,
+
+    /// Entrypoint of the app container, replacing the `ENTRYPOINT` of the image
+    #[serde(default)]
+    entrypoint: Option<Vec<String>>,
+
+    /// User the app container runs as, e.g. `1000:1000`
+    #[serde(default)]
+    user: Option<String>,
+
+    /// Working directory inside the app container
+    #[serde(default)]
+    workdir: Option<String>,
+
     /// Names of environment variables of the application service
     #[serde(default)]
     env: Vec<String>,
@@ -83,10 +126,18 @@ pub struct TopLevelAppConfig {
     #[serde(default)]
     watch: Vec<String>,
 
+    /// Number of app releases to keep on the host for rollbacks
+    #[serde(default = "constants::get_default_keep_releases")]
+    keep_releases: usize,
+
     /// Ports exposed by the application service
     #[serde(default)]
     ports: Vec<u16>,
 
+    /// Health check of the application service
+    #[serde(default)]
+    healthcheck: Option<HealthcheckConfig>,
+
     /// Configuration for Postgres
     #[serde(default)]
     postgres: Option<PostgresConfig>,
@@ -98,6 +149,18 @@ pub struct TopLevelAppConfig {
     /// Configuration for Proxy
     #[serde(default)]
     proxy: Vec<ProxyConfig>,
+
+    /// Image of the Proxy service
+    #[serde(default)]
+    proxy_image: Option<ImageConfig>,
+
+    /// Commands to run at certain points of deploys, e.g. migrations
+    #[serde(default)]
+    hooks: Vec<HookConfig>,
+
+    /// Additional app services sharing the dependencies, e.g. workers
+    #[serde(default)]
+    apps: Vec<ExtraAppConfig>,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -114,10 +177,40 @@ pub struct TopLevelOverrideConfig {
     #[serde(default)]
     context: Option<String>,
 
+    /// Prebuilt image of the app, e.g. `registry.example.com/app:1.2.3`.
+    /// When set, the image is pulled instead of being built
+    #[serde(default)]
+    image: Option<String>,
+
+    /// Where and how the app image is built.
+    /// `build = "local"` only changes the location of the inherited `[build]`
+    #[serde(default, deserialize_with = "deserialize_optional_build")]
+    build: Option<BuildConfigValue>,
+
+    /// Compress the locally built image before sending it to the server
+    #[serde(default)]
+    compress_image: Option<bool>,
+
     /// Paths to .dockerignore files
     #[serde(default)]
     ignore_files: Option<Vec<String>>,
 
+    /// Command of the app container, replacing the `CMD` of the image
+    #[serde(default)]
+    command: Option<Vec<String>>,
+
+    /// Entrypoint of the app container, replacing the `ENTRYPOINT` of the image
+    #[serde(default)]
+    entrypoint: Option<Vec<String>>,
+
+    /// User the app container runs as, e.g. `1000:1000`
+    #[serde(default)]
+    user: Option<String>,
+
+    /// Working directory inside the app container
+    #[serde(default)]
+    workdir: Option<String>,
+
     /// Names of environment variables of the application service
     #[serde(default)]
     env: Option<Vec<String>>,
@@ -139,10 +232,18 @@ pub struct TopLevelOverrideConfig {
     #[serde(default)]
     watch: Option<Vec<String>>,
 
+    /// Number of app releases to keep on the host for rollbacks
+    #[serde(default)]
+    keep_releases: Option<usize>,
+
     /// Ports exposed by the application service
     #[serde(default)]
     ports: Option<Vec<u16>>,
 
+    /// Health check of the application service
+    #[serde(default)]
+    healthcheck: Option<HealthcheckConfig>,
+
     /// Configuration for Postgres
     #[serde(default)]
     postgres: Option<PostgresConfig>,
@@ -154,6 +255,18 @@ pub struct TopLevelOverrideConfig {
     /// Configuration for Proxy
     #[serde(default)]
     proxy: Option<Vec<ProxyConfig>>,
+
+    /// Image of the Proxy service
+    #[serde(default)]
+    proxy_image: Option<ImageConfig>,
+
+    /// Commands to run at certain points of deploys, e.g. migrations
+    #[serde(default)]
+    hooks: Option<Vec<HookConfig>>,
+
+    /// Additional app services sharing the dependencies, e.g. workers
+    #[serde(default)]
+    apps: Option<Vec<ExtraAppConfig>>,
 }
 
 impl AppConfig {
@@ -181,6 +294,37 @@ impl AppConfig {
         )
     }
 
+    pub fn image(&self, context: &OverrideContext) -> Option<&str> {
+        self.resolve_optional_field(
+            context,
+            |config| config.image.as_deref(),
+            |config| config.image.as_deref(),
+        )
+    }
+
+    /// Overrides are applied in order, a table replaces the whole config
+    /// while the location shorthand keeps the rest of it
+    pub fn build(&self, context: &OverrideContext) -> BuildConfig {
+        self.active_overrides(context)
+            .into_iter()
+            .filter_map(|override_config| override_config.config.build.as_ref())
+            .fold(self.config.build.clone(), |build, value| match value {
+                BuildConfigValue::Location(location) => BuildConfig {
+                    location: *location,
+                    ..build
+                },
+                BuildConfigValue::Table(config) => config.clone(),
+            })
+    }
+
+    pub fn compress_image(&self, context: &OverrideContext) -> bool {
+        *self.resolve_field(
+            context,
+            |config| &config.compress_image,
+            |config| config.compress_image.as_ref(),
+        )
+    }
+
     pub fn ignore_files(&self, context: &OverrideContext) -> &[String] {
         self.resolve_field(
             context,
@@ -189,6 +333,38 @@ impl AppConfig {
         )
     }
 
+    pub fn command(&self, context: &OverrideContext) -> Option<&[String]> {
+        self.resolve_optional_field(
+            context,
+            |config| config.command.as_deref(),
+            |config| config.command.as_deref(),
+        )
+    }
+
+    pub fn entrypoint(&self, context: &OverrideContext) -> Option<&[String]> {
+        self.resolve_optional_field(
+            context,
+            |config| config.entrypoint.as_deref(),
+            |config| config.entrypoint.as_deref(),
+        )
+    }
+
+    pub fn user(&self, context: &OverrideContext) -> Option<&str> {
+        self.resolve_optional_field(
+            context,
+            |config| config.user.as_deref(),
+            |config| config.user.as_deref(),
+        )
+    }
+
+    pub fn workdir(&self, context: &OverrideContext) -> Option<&str> {
+        self.resolve_optional_field(
+            context,
+            |config| config.workdir.as_deref(),
+            |config| config.workdir.as_deref(),
+        )
+    }
+
     pub fn env(&self, context: &OverrideContext) -> &[String] {
         self.resolve_field(context, |config| &config.env, |config| config.env.as_ref())
     }
@@ -225,6 +401,14 @@ impl AppConfig {
         )
     }
 
+    pub fn keep_releases(&self, context: &OverrideContext) -> usize {
+        *self.resolve_field(
+            context,
+            |config| &config.keep_releases,
+            |config| config.keep_releases.as_ref(),
+        )
+    }
+
     pub fn ports(&self, context: &OverrideContext) -> &[u16] {
         self.resolve_field(
             context,
@@ -233,6 +417,14 @@ impl AppConfig {
         )
     }
 
+    pub fn healthcheck(&self, context: &OverrideContext) -> Option<&HealthcheckConfig> {
+        self.resolve_optional_field(
+            context,
+            |config| config.healthcheck.as_ref(),
+            |config| config.healthcheck.as_ref(),
+        )
+    }
+
     pub fn postgres(&self, context: &OverrideContext) -> Option<&PostgresConfig> {
         self.resolve_optional_field(
             context,
@@ -257,6 +449,36 @@ impl AppConfig {
         )
     }
 
+    pub fn proxy_image(&self, context: &OverrideContext) -> Option<&ImageConfig> {
+        self.resolve_optional_field(
+            context,
+            |config| config.proxy_image.as_ref(),
+            |config| config.proxy_image.as_ref(),
+        )
+    }
+
+    pub fn hooks(&self, context: &OverrideContext) -> &[HookConfig] {
+        self.resolve_field(
+            context,
+            |config| &config.hooks,
+            |config| config.hooks.as_ref(),
+        )
+    }
+
+    pub fn apps(&self, context: &OverrideContext) -> &[ExtraAppConfig] {
+        self.resolve_field(
+            context,
+            |config| &config.apps,
+            |config| config.apps.as_ref(),
+        )
+    }
+
+    /// Whether any app service is routed by the proxy
+    pub fn has_proxy(&self, context: &OverrideContext) -> bool {
+        !self.proxy(context).is_empty()
+            || self.apps(context).iter().any(|app| !app.proxy().is_empty())
+    }
+
     fn active_overrides(&self, context: &OverrideContext) -> Vec<&OverrideConfig> {
         self.overrides
             .iter()
@@ -328,6 +550,276 @@ impl AppConfig {
     }
 }
 
+/// App service in addition to the top-level one, with its own container.
+/// Without its own `dockerfile`, `context` and `image` it's built like the top-level app
+#[derive(Debug, Deserialize, Default)]
+pub struct ExtraAppConfig {
+    /// Name of the app, used in its container name
+    name: String,
+
+    /// Relative path to the Dockerfile
+    #[serde(default)]
+    dockerfile: Option<String>,
+
+    /// Docker context
+    #[serde(default)]
+    context: Option<String>,
+
+    /// Prebuilt image of the app
+    #[serde(default)]
+    image: Option<String>,
+
+    /// Command of the container, replacing the `CMD` of the image
+    #[serde(default)]
+    command: Option<Vec<String>>,
+
+    /// Entrypoint of the container, replacing the `ENTRYPOINT` of the image
+    #[serde(default)]
+    entrypoint: Option<Vec<String>>,
+
+    /// User the container runs as, e.g. `1000:1000`
+    #[serde(default)]
+    user: Option<String>,
+
+    /// Working directory inside the container
+    #[serde(default)]
+    workdir: Option<String>,
+
+    /// Names of environment variables of the app
+    #[serde(default)]
+    env: Vec<String>,
+
+    /// Paths to persistent volumes inside the container
+    #[serde(default)]
+    volumes: Vec<String>,
+
+    /// Ports exposed by the app
+    #[serde(default)]
+    ports: Vec<u16>,
+
+    /// Health check of the app
+    #[serde(default)]
+    healthcheck: Option<HealthcheckConfig>,
+
+    /// Domains routed to the app
+    #[serde(default)]
+    proxy: Vec<ProxyConfig>,
+}
+
+impl ExtraAppConfig {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn dockerfile(&self) -> Option<&str> {
+        self.dockerfile.as_deref()
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    pub fn command(&self) -> Option<&[String]> {
+        self.command.as_deref()
+    }
+
+    pub fn entrypoint(&self) -> Option<&[String]> {
+        self.entrypoint.as_deref()
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn workdir(&self) -> Option<&str> {
+        self.workdir.as_deref()
+    }
+
+    pub fn env(&self) -> &[String] {
+        &self.env
+    }
+
+    pub fn volumes(&self) -> &[String] {
+        &self.volumes
+    }
+
+    pub fn ports(&self) -> &[u16] {
+        &self.ports
+    }
+
+    pub fn healthcheck(&self) -> Option<&HealthcheckConfig> {
+        self.healthcheck.as_ref()
+    }
+
+    pub fn proxy(&self) -> &[ProxyConfig] {
+        &self.proxy
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct HealthcheckConfig {
+    /// HTTP path to request on `port`, e.g. `/health`.
+    /// Any 2xx or 3xx response is considered healthy
+    #[serde(default)]
+    http: Option<String>,
+
+    /// Port inside the container used for the `http` check
+    #[serde(default)]
+    port: Option<u16>,
+
+    /// Port inside the container which should accept TCP connections
+    #[serde(default)]
+    tcp: Option<u16>,
+
+    /// Shell command executed inside the container, healthy on zero exit code
+    #[serde(default)]
+    command: Option<String>,
+
+    /// Seconds to wait for the check to pass
+    #[serde(default = "constants::get_default_healthcheck_timeout")]
+    timeout: u64,
+
+    /// Seconds between attempts
+    #[serde(default = "constants::get_default_healthcheck_interval")]
+    interval: u64,
+}
+
+impl HealthcheckConfig {
+    pub fn http(&self) -> Option<&str> {
+        self.http.as_deref()
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn tcp(&self) -> Option<u16> {
+        self.tcp
+    }
+
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    pub fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct BuildConfig {
+    /// Docker daemon building the image when deploying
+    #[serde(default)]
+    location: BuildLocation,
+
+    /// Build args, either literal values or taken from the env: `{ env = "NPM_TOKEN" }`
+    #[serde(default)]
+    args: BTreeMap<String, BuildArgConfig>,
+
+    /// Stage of a multi-stage Dockerfile to build
+    #[serde(default)]
+    target: Option<String>,
+
+    /// Platform to build for, e.g. `linux/amd64`
+    #[serde(default)]
+    platform: Option<String>,
+
+    /// Labels added to the image
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+
+    /// Images used as cache sources
+    #[serde(default)]
+    cache_from: Vec<String>,
+
+    /// Don't use the cache of previous builds
+    #[serde(default)]
+    no_cache: bool,
+}
+
+impl BuildConfig {
+    pub fn location(&self) -> BuildLocation {
+        self.location
+    }
+
+    pub fn args(&self) -> &BTreeMap<String, BuildArgConfig> {
+        &self.args
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
+    pub fn cache_from(&self) -> &[String] {
+        &self.cache_from
+    }
+
+    pub fn no_cache(&self) -> bool {
+        self.no_cache
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum BuildArgConfig {
+    Value(String),
+
+    /// Name of the environment variable holding the value
+    Env {
+        env: String,
+    },
+}
+
+/// Accepts both the location shorthand and the full table
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum BuildConfigValue {
+    Location(BuildLocation),
+    Table(BuildConfig),
+}
+
+impl From<BuildConfigValue> for BuildConfig {
+    fn from(value: BuildConfigValue) -> Self {
+        match value {
+            BuildConfigValue::Location(location) => BuildConfig {
+                location,
+                ..Default::default()
+            },
+            BuildConfigValue::Table(config) => config,
+        }
+    }
+}
+
+fn deserialize_build<'de, D>(deserializer: D) -> Result<BuildConfig, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    BuildConfigValue::deserialize(deserializer).map(Into::into)
+}
+
+fn deserialize_optional_build<'de, D>(deserializer: D) -> Result<Option<BuildConfigValue>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    BuildConfigValue::deserialize(deserializer).map(Some)
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct PostgresConfig {
     /// Name of the environment variable for the database URL
@@ -337,6 +829,19 @@ pub struct PostgresConfig {
     /// Name of the database
     #[serde(default)]
     database_name: Option<String>,
+
+    /// Image and version of Postgres
+    #[serde(flatten)]
+    image: ImageConfig,
+
+    /// Name of the database user
+    #[serde(default)]
+    user: Option<String>,
+
+    /// Name of the environment variable containing the database password.
+    /// If omitted, a password is generated and persisted on the target host
+    #[serde(default)]
+    password_env: Option<String>,
 }
 
 impl PostgresConfig {
@@ -347,6 +852,18 @@ impl PostgresConfig {
     pub fn database_name(&self) -> Option<&str> {
         self.database_name.as_deref()
     }
+
+    pub fn image(&self) -> &ImageConfig {
+        &self.image
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn password_env(&self) -> Option<&str> {
+        self.password_env.as_deref()
+    }
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -354,22 +871,142 @@ pub struct KeydbConfig {
     /// Name of the environment variable for the database URL
     #[serde(default)]
     expose_url_to_env: Option<String>,
+
+    /// Image and version of Keydb
+    #[serde(flatten)]
+    image: ImageConfig,
 }
 
 impl KeydbConfig {
     pub fn expose_url_to_env(&self) -> Option<&str> {
         self.expose_url_to_env.as_deref()
     }
+
+    pub fn image(&self) -> &ImageConfig {
+        &self.image
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
+pub struct ImageConfig {
+    /// Name of the docker image
+    #[serde(default)]
+    image: Option<String>,
+
+    /// Tag of the docker image
+    #[serde(default)]
+    version: Option<String>,
+}
+
+impl ImageConfig {
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct ProxyConfig {
     /// Domain name of the proxy
     /// Note that SSL will be generated automatically
     pub domain: String,
 
+    /// Port inside the container, receives all requests not matched by `routes`
+    pub port: u16,
+
+    /// Paths sent to other ports inside the container
+    #[serde(default)]
+    pub routes: Vec<ProxyRouteConfig>,
+
+    /// Redirect `www.<domain>` to the domain
+    #[serde(default)]
+    pub redirect_www: bool,
+
+    /// Headers added to every response
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// Protect the domain with HTTP basic auth
+    #[serde(default)]
+    pub basic_auth: Option<BasicAuthConfig>,
+
+    /// Maximum size of request bodies, e.g. `10MB`
+    #[serde(default)]
+    pub max_body_size: Option<String>,
+
+    /// Maximum duration of websocket and other streaming connections, e.g. `1h`
+    #[serde(default)]
+    pub websocket_timeout: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ProxyRouteConfig {
+    /// Path prefix, e.g. `/api`
+    pub path: String,
+
     /// Port inside the container
     pub port: u16,
+
+    /// Remove the path prefix before passing the request to the app
+    #[serde(default)]
+    pub strip_prefix: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BasicAuthConfig {
+    pub user: String,
+
+    /// Bcrypt hash of the password, e.g. from `caddy hash-password`
+    pub password_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HookConfig {
+    /// Point of the deploy at which the hook runs
+    pub phase: HookPhase,
+
+    /// Shell command to run
+    pub command: String,
+
+    /// Running service to execute the command in.
+    /// If omitted, the command runs in a one-off container from the app image
+    #[serde(default)]
+    pub service: Option<HookService>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPhase {
+    /// After the app image is built and dependencies are ready,
+    /// but before the new app container is started
+    PreApp,
+
+    /// After the new app container is started
+    PostApp,
+
+    /// Before the services are stopped
+    PreStop,
+}
+
+impl fmt::Display for HookPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookPhase::PreApp => write!(f, "pre_app"),
+            HookPhase::PostApp => write!(f, "post_app"),
+            HookPhase::PreStop => write!(f, "pre_stop"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookService {
+    App,
+    Postgres,
+    Keydb,
 }
 
 #[cfg(test)]
@@ -420,4 +1057,152 @@ mod tests {
         assert_eq!("Dockerfile", app_config.dockerfile(&other_context));
         assert_eq!("dploy-test", app_config.name(&other_context));
     }
+
+    #[test]
+    fn test_build_config() {
+        let context = OverrideContext {
+            namespace: "default".to_owned(),
+            command: OverrideRuleCommand::Deploy,
+        };
+
+        let app_config: AppConfig = toml::from_str(
+            r#"
+            name = "dploy-test"
+            build = "local"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(BuildLocation::Local, app_config.build(&context).location());
+
+        let app_config: AppConfig = toml::from_str(
+            r#"
+            name = "dploy-test"
+
+            [build]
+            target = "runtime"
+            args = { VERSION = "1.0", NPM_TOKEN = { env = "NPM_TOKEN" } }
+            "#,
+        )
+        .unwrap();
+
+        let build = app_config.build(&context);
+
+        assert_eq!(BuildLocation::Remote, build.location());
+        assert_eq!(Some("runtime"), build.target());
+        assert!(matches!(
+            build.args().get("NPM_TOKEN"),
+            Some(BuildArgConfig::Env { env }) if env == "NPM_TOKEN"
+        ));
+
+        let app_config: AppConfig = toml::from_str(
+            r#"
+            name = "dploy-test"
+
+            [build]
+            target = "runtime"
+            args = { VERSION = "1.0" }
+
+            [[override]]
+            for = { namespace = "default" }
+            build = "local"
+
+            [[override]]
+            for = { namespace = "staging" }
+            build = { target = "debug" }
+            "#,
+        )
+        .unwrap();
+
+        let build = app_config.build(&context);
+
+        // the location shorthand keeps the inherited table
+        assert_eq!(BuildLocation::Local, build.location());
+        assert_eq!(Some("runtime"), build.target());
+        assert!(build.args().contains_key("VERSION"));
+
+        let staging_context = OverrideContext {
+            namespace: "staging".to_owned(),
+            command: OverrideRuleCommand::Deploy,
+        };
+        let build = app_config.build(&staging_context);
+
+        // a table replaces the whole config
+        assert_eq!(BuildLocation::Remote, build.location());
+        assert_eq!(Some("debug"), build.target());
+        assert!(build.args().is_empty());
+    }
+
+    #[test]
+    fn test_apps_config() {
+        let context = OverrideContext {
+            namespace: "default".to_owned(),
+            command: OverrideRuleCommand::Deploy,
+        };
+
+        let app_config: AppConfig = toml::from_str(
+            r#"
+            name = "dploy-test"
+
+            [[apps]]
+            name = "worker"
+            command = ["bin/worker", "--queue", "default"]
+            env = ["QUEUE_URL"]
+
+            [[apps]]
+            name = "admin"
+            dockerfile = "admin/Dockerfile"
+            ports = [8080]
+            proxy = [{ domain = "admin.example.com", port = 8080 }]
+            "#,
+        )
+        .unwrap();
+
+        let apps = app_config.apps(&context);
+
+        assert_eq!(2, apps.len());
+        assert_eq!("worker", apps[0].name());
+        assert_eq!(None, apps[0].dockerfile());
+        assert_eq!(["QUEUE_URL".to_owned()], apps[0].env());
+        assert_eq!(Some(3), apps[0].command().map(<[String]>::len));
+        assert_eq!(Some("admin/Dockerfile"), apps[1].dockerfile());
+        assert!(app_config.proxy(&context).is_empty());
+        assert!(app_config.has_proxy(&context));
+    }
+
+    #[test]
+    fn test_container_overrides() {
+        let app_config: AppConfig = toml::from_str(
+            r#"
+            name = "dploy-test"
+            user = "1000:1000"
+            workdir = "/app"
+
+            [[override]]
+            for = { namespace = "worker" }
+            command = ["bin/worker"]
+            entrypoint = ["/usr/bin/tini", "--"]
+            "#,
+        )
+        .unwrap();
+
+        let context = OverrideContext {
+            namespace: "default".to_owned(),
+            command: OverrideRuleCommand::Deploy,
+        };
+
+        let worker_context = OverrideContext {
+            namespace: "worker".to_owned(),
+            command: OverrideRuleCommand::Deploy,
+        };
+
+        assert_eq!(None, app_config.command(&context));
+        assert_eq!(
+            Some(&["bin/worker".to_owned()][..]),
+            app_config.command(&worker_context)
+        );
+        assert_eq!(2, app_config.entrypoint(&worker_context).unwrap().len());
+        assert_eq!(Some("1000:1000"), app_config.user(&worker_context));
+        assert_eq!(Some("/app"), app_config.workdir(&context));
+    }
 }
@@ -3,6 +3,19 @@ pub const DEFAULT_DOCKERFILE_NAME: &str = "Dockerfile";
 pub const DEFAULT_DOTENV_FILE_NAME: &str = ".env";
 pub const DEFAULT_DOCKER_CONTEXT: &str = ".";
 pub const DEFAULT_DOCKER_IGNORE_FILE: &str = ".dockerignore";
+pub const DEFAULT_HEALTHCHECK_TIMEOUT: u64 = 60;
+pub const DEFAULT_HEALTHCHECK_INTERVAL: u64 = 2;
+pub const DEFAULT_KEEP_RELEASES: usize = 5;
+
+/// Output of the last image build, relative to the config
+pub const BUILD_LOG_PATH: &str = ".dploy/build.log";
+
+/// Env vars with the credentials of the registry of the prebuilt app image
+pub const REGISTRY_USERNAME_ENV: &str = "DPLOY_REGISTRY_USERNAME";
+pub const REGISTRY_PASSWORD_ENV: &str = "DPLOY_REGISTRY_PASSWORD";
+
+/// Directory on the docker host keeping the data of all projects
+pub const DPLOY_DIR: &str = "/var/lib/dploy";
 
 pub fn get_default_dockerfile_name() -> String {
     DEFAULT_DOCKERFILE_NAME.to_string()
@@ -19,3 +32,19 @@ pub fn get_default_docker_context() -> String {
 pub fn get_default_ignore_files() -> Vec<String> {
     vec![DEFAULT_DOCKER_IGNORE_FILE.to_string()]
 }
+
+pub fn get_default_healthcheck_timeout() -> u64 {
+    DEFAULT_HEALTHCHECK_TIMEOUT
+}
+
+pub fn get_default_healthcheck_interval() -> u64 {
+    DEFAULT_HEALTHCHECK_INTERVAL
+}
+
+pub fn get_default_compress_image() -> bool {
+    true
+}
+
+pub fn get_default_keep_releases() -> usize {
+    DEFAULT_KEEP_RELEASES
+}
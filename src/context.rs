@@ -3,16 +3,20 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use anyhow::{bail, Result};
 use bollard::models;
 
 use crate::{
-    cli::{Args, Command},
+    cli::{Args, Command, DeployCommand, ServiceSelector},
     config::{self, AppConfig},
-    constants,
+    constants, docker,
     services::{self, ServiceKind},
     utils,
 };
 
+/// Project name of the containers shared by all projects of the host
+pub const SINGLETON_PROJECT: &str = "dploy-singleton";
+
 #[derive(Debug)]
 pub struct Context {
     args: Args,
@@ -48,15 +52,18 @@ impl Context {
         &self.config_path
     }
 
-    pub fn config_dir_relative_to_docker_context(&self) -> PathBuf {
-        let config_path = PathBuf::from(".").canonicalize().unwrap();
-        let docker_context = PathBuf::from(self.app_config().context(&self.override_context))
-            .canonicalize()
-            .unwrap();
+    pub fn config_dir_relative_to_docker_context(&self, docker_context: &str) -> Result<PathBuf> {
+        let config_path = PathBuf::from(".").canonicalize()?;
+        let canonical_docker_context = match PathBuf::from(docker_context).canonicalize() {
+            Ok(path) => path,
+            Err(error) => bail!("Docker context {docker_context} is not accessible: {error}"),
+        };
 
-        let diff = pathdiff::diff_paths(&config_path, &docker_context).unwrap();
+        let Some(diff) = pathdiff::diff_paths(&config_path, &canonical_docker_context) else {
+            bail!("Docker context {docker_context} can't be resolved relative to the config");
+        };
 
-        PathBuf::from(self.app_config().context(&self.override_context)).join(diff)
+        Ok(PathBuf::from(docker_context).join(diff))
     }
 
     pub fn override_context(&self) -> &config::OverrideContext {
@@ -71,12 +78,9 @@ impl Context {
         &self.app_config
     }
 
+    /// Container of the top-level app for `ServiceKind::App`
     pub fn container_name_of(&self, service_kind: ServiceKind) -> String {
-        let prefix = if service_kind.is_singleton() {
-            "dploy-singleton"
-        } else {
-            self.app_config.name(&self.override_context)
-        };
+        let (project, namespace) = self.scope_of(service_kind);
 
         let suffix = {
             use ServiceKind::*;
@@ -89,29 +93,127 @@ impl Context {
             }
         };
 
-        let namespace = if service_kind == services::ServiceKind::Proxy {
+        format!("{project}_{suffix}_{namespace}")
+    }
+
+    /// Container of the app with the given name,
+    /// the top-level app is named after the project
+    pub fn app_container_name_of(&self, app_name: &str) -> String {
+        let (project, namespace) = self.scope_of(ServiceKind::App);
+
+        format!("{project}_{app_name}_{namespace}")
+    }
+
+    /// Containers of the top-level app and of all `[[apps]]` entries
+    pub fn app_container_names(&self) -> Vec<String> {
+        self.app_routes()
+            .into_iter()
+            .map(|(container_name, _)| container_name)
+            .collect()
+    }
+
+    /// Containers of all apps with the domains routed to them
+    pub fn app_routes(&self) -> Vec<(String, &[config::ProxyConfig])> {
+        std::iter::once((
+            self.container_name_of(ServiceKind::App),
+            self.app_config.proxy(&self.override_context),
+        ))
+        .chain(
+            self.app_config
+                .apps(&self.override_context)
+                .iter()
+                .map(|app| (self.app_container_name_of(app.name()), app.proxy())),
+        )
+        .collect()
+    }
+
+    /// Dependencies configured for the namespace
+    pub fn dependency_kinds(&self) -> Vec<ServiceKind> {
+        let mut service_kinds = vec![];
+
+        if self.app_config.postgres(&self.override_context).is_some() {
+            service_kinds.push(ServiceKind::Postgres);
+        }
+
+        if self.app_config.keydb(&self.override_context).is_some() {
+            service_kinds.push(ServiceKind::Keydb);
+        }
+
+        service_kinds
+    }
+
+    /// Container of the service selected with `-s`
+    pub fn container_name_of_selected(&self, service: &ServiceSelector) -> Result<String> {
+        match service {
+            ServiceSelector::Kind(service_kind) => Ok(self.container_name_of(*service_kind)),
+            ServiceSelector::App(app_name) => {
+                let is_known = app_name == self.app_config.name(&self.override_context)
+                    || self
+                        .app_config
+                        .apps(&self.override_context)
+                        .iter()
+                        .any(|app| app.name() == app_name);
+
+                if !is_known {
+                    bail!("{app_name} is neither a service nor an app of the project");
+                }
+
+                Ok(self.app_container_name_of(app_name))
+            }
+        }
+    }
+
+    /// Labels identifying the container of the service, used by `dploy ls`
+    pub fn labels_of(&self, service_kind: ServiceKind) -> HashMap<String, String> {
+        let (project, namespace) = self.scope_of(service_kind);
+
+        HashMap::from([
+            (services::PROJECT_LABEL.to_owned(), project.to_owned()),
+            (services::NAMESPACE_LABEL.to_owned(), namespace.to_owned()),
+            (services::KIND_LABEL.to_owned(), service_kind.to_string()),
+            (
+                services::VERSION_LABEL.to_owned(),
+                env!("CARGO_PKG_VERSION").to_owned(),
+            ),
+        ])
+    }
+
+    /// Project and namespace the service belongs to.
+    /// Singleton services are shared by all projects of the host
+    fn scope_of(&self, service_kind: ServiceKind) -> (&str, &str) {
+        let is_singleton = service_kind.is_singleton() && !self.is_local_proxy();
+
+        if is_singleton {
             // proxy is not namespaced because it's exposed to the host's network
-            constants::DEFAULT_NAMESPACE
+            (SINGLETON_PROJECT, constants::DEFAULT_NAMESPACE)
         } else {
-            self.namespace()
-        };
+            (
+                self.app_config.name(&self.override_context),
+                self.namespace(),
+            )
+        }
+    }
 
-        format!("{prefix}_{suffix}_{namespace}")
+    /// Directory containing all persistent volumes of the service
+    pub fn volumes_dir_of(&self, service_kind: ServiceKind) -> PathBuf {
+        self.volumes_dir_of_container(&self.container_name_of(service_kind))
+    }
+
+    pub fn volumes_dir_of_container(&self, container_name: &str) -> PathBuf {
+        self.get_dploy_dir().join("volumes").join(container_name)
     }
 
     pub fn volume_path_of(&self, service_kind: ServiceKind, path: impl AsRef<Path>) -> PathBuf {
-        let volume_path = self
-            .get_dploy_dir()
-            .join("volumes")
-            .join(self.container_name_of(service_kind))
-            .join(
-                path.as_ref()
-                    .to_string_lossy()
-                    .replace('\\', "/")
-                    .replace('/', "$__$"),
-            );
+        self.volumes_dir_of(service_kind)
+            .join(Self::volume_name_of(path))
+    }
 
-        volume_path
+    /// Name of the directory inside `volumes_dir_of` used for the given inner path
+    pub fn volume_name_of(path: impl AsRef<Path>) -> String {
+        path.as_ref()
+            .to_string_lossy()
+            .replace('\\', "/")
+            .replace('/', "$__$")
     }
 
     pub fn should_expose_to_host(&self) -> bool {
@@ -141,8 +243,34 @@ impl Context {
     pub fn should_create_proxy_service(&self) -> bool {
         use Command::*;
 
-        matches!(self.args.command(), Deploy { command: None, .. })
-            && self.app_config().proxy(self.override_context()).len() > 0
+        matches!(
+            self.args.command(),
+            Deploy {
+                command: None | Some(DeployCommand::Rollback { .. }),
+                ..
+            } | Run {
+                command: None,
+                proxy: true,
+                ..
+            }
+        ) && self.app_config().has_proxy(self.override_context())
+    }
+
+    /// With `run --proxy` the proxy is started per project on free host ports,
+    /// serving `*.localhost` domains with certificates of caddy's internal CA
+    pub fn is_local_proxy(&self) -> bool {
+        use Command::*;
+
+        matches!(self.args.command(), Run { proxy: true, .. })
+    }
+
+    /// Deploys build the image with the local daemon and send it to the server
+    pub fn should_build_locally(&self) -> bool {
+        use Command::*;
+
+        matches!(self.args.command(), Deploy { .. })
+            && self.app_config().build(self.override_context()).location()
+                == config::BuildLocation::Local
     }
 
     pub fn should_generate_env_file(&self) -> bool {
@@ -183,9 +311,16 @@ impl Context {
     }
 
     pub fn mount(&self, service_kind: ServiceKind, inner_path: &str) -> models::Mount {
+        self.container_mount(&self.container_name_of(service_kind), inner_path)
+    }
+
+    /// Mount of a persistent volume of the container, used by apps which are
+    /// not identified by their service kind alone
+    pub fn container_mount(&self, container_name: &str, inner_path: &str) -> models::Mount {
         models::Mount {
             source: Some(
-                self.volume_path_of(service_kind, inner_path)
+                self.volumes_dir_of_container(container_name)
+                    .join(Self::volume_name_of(inner_path))
                     .to_string_lossy()
                     .to_string(),
             ),
@@ -202,16 +337,23 @@ impl Context {
         }
     }
 
-    pub fn host_port_binding_of(
+    /// The host port of an existing container is kept,
+    /// otherwise its config would change on every run and it would be recreated
+    pub async fn host_port_binding_of(
         &self,
+        docker: &bollard::Docker,
         service_kind: ServiceKind,
         inner_port: u16,
-    ) -> HostPortBinding {
-        HostPortBinding::new(
-            &self.container_name_of(service_kind),
+    ) -> Result<HostPortBinding> {
+        let container_name = self.container_name_of(service_kind);
+        let existing_host_ports = docker::existing_host_ports(docker, &container_name).await?;
+
+        Ok(HostPortBinding::new(
+            &container_name,
             inner_port,
             self.args.command(),
-        )
+            existing_host_ports.get(&inner_port).copied(),
+        ))
     }
 
     pub fn ssh_credentials(&self) -> Option<SshCredentials> {
@@ -239,7 +381,7 @@ impl Context {
     }
 
     fn get_dploy_dir(&self) -> PathBuf {
-        PathBuf::from("/var/lib/dploy")
+        PathBuf::from(constants::DPLOY_DIR)
     }
 }
 
@@ -259,11 +401,18 @@ pub struct HostPortBinding {
 }
 
 impl HostPortBinding {
-    pub fn new(container_name: &str, internal_port: u16, command: &Command) -> Self {
+    pub fn new(
+        container_name: &str,
+        internal_port: u16,
+        command: &Command,
+        existing_host_port: Option<u16>,
+    ) -> Self {
         use Command::*;
 
         let host_port = match command {
-            Dev { .. } | Run { .. } => Some(utils::network::free_port()),
+            Dev { .. } | Run { .. } => {
+                Some(existing_host_port.unwrap_or_else(utils::network::free_port))
+            }
             _ => None,
         };
 
@@ -1,9 +1,10 @@
 use std::{
+    collections::HashMap,
     io::{Read, Write},
     time::Duration,
 };
 
-use futures::StreamExt;
+use futures::{StreamExt, TryStreamExt};
 use termion::raw::IntoRawMode;
 use tokio::io::AsyncWriteExt;
 
@@ -20,36 +21,68 @@ pub async fn get_default_docker_client() -> Result<bollard::Docker> {
 pub async fn get_docker_client_with_session(
     context: &context::Context,
 ) -> Result<(bollard::Docker, openssh::Session)> {
-    let (docker, session) = ssh::get_remote_docker_client(context).await?;
+    let Some(credentials) = context.ssh_credentials() else {
+        bail!("No SSH credentials provided")
+    };
+
+    let (docker, session) =
+        ssh::get_remote_docker_client(&credentials, context.args().is_json_output()).await?;
 
     Ok((docker, session))
 }
 
+/// Client of the remote host when credentials are given, otherwise of the local one.
+/// The session must be kept open while the client is used
+pub async fn get_host_docker_client(
+    credentials: Option<&context::SshCredentials>,
+) -> Result<(bollard::Docker, Option<openssh::Session>)> {
+    match credentials {
+        Some(credentials) => {
+            let (docker, session) = ssh::get_remote_docker_client(credentials, false).await?;
+
+            Ok((docker, Some(session)))
+        }
+        None => Ok((get_default_docker_client().await?, None)),
+    }
+}
+
+/// Exit code and error output of a command executed in a container
+#[derive(Debug, Clone)]
+pub struct ExecOutput {
+    pub exit_code: i64,
+    pub stderr: String,
+}
+
+impl ExecOutput {
+    pub fn is_success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+/// Runs the command without a terminal and waits until it finishes
 pub async fn exec_command_detached(
     docker: &bollard::Docker,
     container_name: &str,
     command: &str,
-) -> Result<()> {
-    let result = docker
-        .create_exec(
-            container_name,
-            bollard::exec::CreateExecOptions::<String> {
-                cmd: Some(["sh", "-c", command].into_iter().map(Into::into).collect()),
-                ..Default::default()
-            },
-        )
-        .await?;
-
-    docker.start_exec(&result.id, None).await?;
+) -> Result<ExecOutput> {
+    let output = exec_command_output(docker, container_name, command).await?;
 
-    Ok(())
+    Ok(ExecOutput {
+        exit_code: output.exit_code,
+        stderr: output.stderr,
+    })
 }
 
+/// Runs the command with the stdin and stdout of the CLI attached.
+/// A tty is used only when the CLI itself runs in a terminal,
+/// otherwise stderr is kept separate so the command can be used in scripts
 pub async fn exec_command_attached(
     docker: &bollard::Docker,
     container_name: &str,
     command: &str,
-) -> Result<()> {
+) -> Result<ExecOutput> {
+    let is_tty = termion::is_tty(&std::io::stdin()) && termion::is_tty(&std::io::stdout());
+
     let exec = docker
         .create_exec(
             container_name,
@@ -58,7 +91,7 @@ pub async fn exec_command_attached(
                 attach_stdout: Some(true),
                 attach_stderr: Some(true),
                 attach_stdin: Some(true),
-                tty: Some(true),
+                tty: Some(is_tty),
                 ..Default::default()
             },
         )
@@ -68,38 +101,111 @@ pub async fn exec_command_attached(
         .start_exec(&exec.id, Some(bollard::exec::StartExecOptions::default()))
         .await?;
 
+    let mut stderr = String::new();
+
     match result {
         bollard::exec::StartExecResults::Attached {
             mut input,
             mut output,
         } => {
             // pipe stdin into the docker exec stream input
-            tokio::spawn(async move {
-                let mut stdin = termion::async_stdin().bytes();
+            let stdin_handle = tokio::spawn(async move {
+                let mut stdin = termion::async_stdin();
+                let mut buffer = [0u8; 1024];
 
                 loop {
-                    if let Some(Ok(byte)) = stdin.next() {
-                        input.write_all(&[byte]).await.ok();
-                    } else {
-                        tokio::time::sleep(Duration::from_nanos(10)).await;
+                    match stdin.read(&mut buffer) {
+                        Ok(count) if count > 0 => {
+                            input.write_all(&buffer[..count]).await.ok();
+                        }
+                        _ => {
+                            tokio::time::sleep(Duration::from_nanos(10)).await;
+                        }
                     }
                 }
             });
 
             // set stdout in raw mode so we can do tty stuff
             let stdout = std::io::stdout();
-            let mut stdout = stdout.lock().into_raw_mode()?;
+            let mut stdout: Box<dyn Write> = if is_tty {
+                Box::new(stdout.lock().into_raw_mode()?)
+            } else {
+                Box::new(stdout.lock())
+            };
 
             // pipe docker exec output into stdout
             while let Some(Ok(output)) = output.next().await {
-                stdout.write_all(output.into_bytes().as_ref())?;
-                stdout.flush()?;
+                match output {
+                    bollard::container::LogOutput::StdErr { message } => {
+                        stderr.push_str(&String::from_utf8_lossy(&message));
+                        std::io::stderr().write_all(&message)?;
+                    }
+                    output => {
+                        stdout.write_all(output.into_bytes().as_ref())?;
+                        stdout.flush()?;
+                    }
+                }
             }
+
+            stdin_handle.abort();
         }
         _ => unreachable!(),
     }
 
-    Ok(())
+    let exit_code = docker
+        .inspect_exec(&exec.id)
+        .await?
+        .exit_code
+        .unwrap_or_default();
+
+    Ok(ExecOutput { exit_code, stderr })
+}
+
+/// Runs the command to completion and captures its output and exit code
+pub async fn exec_command_output(
+    docker: &bollard::Docker,
+    container_name: &str,
+    command: &str,
+) -> Result<ContainerOutput> {
+    let exec = docker
+        .create_exec(
+            container_name,
+            bollard::exec::CreateExecOptions::<String> {
+                cmd: Some(["sh", "-c", command].into_iter().map(Into::into).collect()),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+
+    if let bollard::exec::StartExecResults::Attached { mut output, .. } =
+        docker.start_exec(&exec.id, None).await?
+    {
+        while let Some(chunk) = output.next().await {
+            match chunk? {
+                bollard::container::LogOutput::StdErr { message } => {
+                    stderr.push_str(&String::from_utf8_lossy(&message))
+                }
+                other => stdout.push_str(&String::from_utf8_lossy(&other.into_bytes())),
+            }
+        }
+    }
+
+    let exit_code = docker
+        .inspect_exec(&exec.id)
+        .await?
+        .exit_code
+        .unwrap_or_default();
+
+    Ok(ContainerOutput {
+        exit_code,
+        stdout,
+        stderr,
+    })
 }
 
 /// This version ignores the error if the container is not found
@@ -107,7 +213,13 @@ pub async fn inspect_container(
     docker: &bollard::Docker,
     container_name: &str,
 ) -> Result<Option<bollard::models::ContainerInspectResponse>> {
-    match docker.inspect_container(container_name, None).await {
+    match docker
+        .inspect_container(
+            container_name,
+            None::<bollard::query_parameters::InspectContainerOptions>,
+        )
+        .await
+    {
         Ok(container) => Ok(Some(container)),
         Err(bollard::errors::Error::DockerResponseServerError {
             status_code: 404, ..
@@ -116,6 +228,30 @@ pub async fn inspect_container(
     }
 }
 
+/// Host ports of the existing container, keyed by the port inside it
+pub async fn existing_host_ports(
+    docker: &bollard::Docker,
+    container_name: &str,
+) -> Result<HashMap<u16, u16>> {
+    let port_bindings = inspect_container(docker, container_name)
+        .await?
+        .and_then(|container| container.host_config)
+        .and_then(|host_config| host_config.port_bindings)
+        .unwrap_or_default();
+
+    Ok(port_bindings
+        .into_iter()
+        .filter_map(|(container_port, bindings)| {
+            let inner_port = container_port.split('/').next()?.parse().ok()?;
+            let host_port = bindings?
+                .into_iter()
+                .find_map(|binding| binding.host_port?.parse().ok())?;
+
+            Some((inner_port, host_port))
+        })
+        .collect())
+}
+
 pub async fn check_container_running(
     docker: &bollard::Docker,
     container_name: &str,
@@ -124,7 +260,182 @@ pub async fn check_container_running(
         .await
         .map(|container| {
             container.is_some_and(|container| {
-                container.state.is_some_and(|state| state.running.is_some())
+                container
+                    .state
+                    .is_some_and(|state| state.running.unwrap_or(false))
             })
         })
 }
+
+/// Writes the file into the directory of the container through the archive API,
+/// so the contents never pass through a shell. Works for stopped containers as well
+pub async fn upload_file(
+    docker: &bollard::Docker,
+    container_name: &str,
+    dir: &str,
+    file_name: &str,
+    contents: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        chrono::Utc::now()
+            .timestamp()
+            .try_into()
+            .unwrap_or_default(),
+    );
+    header.set_cksum();
+
+    let mut archive = tar::Builder::new(Vec::new());
+    archive.append_data(&mut header, file_name, contents)?;
+    let bytes = archive.into_inner()?;
+
+    docker
+        .upload_to_container(
+            container_name,
+            Some(bollard::query_parameters::UploadToContainerOptions {
+                path: dir.to_owned(),
+                ..Default::default()
+            }),
+            bollard::body_full(bytes.into()),
+        )
+        .await
+        .with_context(|| format!("Could not upload {file_name} to {container_name}"))?;
+
+    Ok(())
+}
+
+/// Pulls the image unless it is already present on the host
+pub async fn ensure_image(docker: &bollard::Docker, image_name: &str, tag: &str) -> Result<()> {
+    let reference = format!("{image_name}:{tag}");
+
+    match docker.inspect_image(&reference).await {
+        Ok(_) => return Ok(()),
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    docker
+        .create_image(
+            Some(bollard::query_parameters::CreateImageOptions {
+                from_image: Some(image_name.to_owned()),
+                tag: Some(tag.to_owned()),
+                ..Default::default()
+            }),
+            None,
+            None,
+        )
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct ContainerOutput {
+    pub exit_code: i64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs a one-off container until it exits, collects its output and removes it
+pub async fn run_container_to_completion(
+    docker: &bollard::Docker,
+    container_name: &str,
+    config: bollard::models::ContainerCreateBody,
+) -> Result<ContainerOutput> {
+    remove_container_if_exists(docker, container_name).await?;
+
+    docker
+        .create_container(
+            Some(bollard::query_parameters::CreateContainerOptions {
+                name: Some(container_name.to_owned()),
+                ..Default::default()
+            }),
+            config,
+        )
+        .await?;
+
+    let result = wait_and_collect_output(docker, container_name).await;
+
+    remove_container_if_exists(docker, container_name).await?;
+
+    result
+}
+
+async fn wait_and_collect_output(
+    docker: &bollard::Docker,
+    container_name: &str,
+) -> Result<ContainerOutput> {
+    docker
+        .start_container(
+            container_name,
+            None::<bollard::query_parameters::StartContainerOptions>,
+        )
+        .await?;
+
+    let mut exit_code = 0;
+    let mut wait = docker.wait_container(
+        container_name,
+        None::<bollard::query_parameters::WaitContainerOptions>,
+    );
+
+    while let Some(response) = wait.next().await {
+        match response {
+            Ok(response) => exit_code = response.status_code,
+            Err(bollard::errors::Error::DockerContainerWaitError { code, .. }) => exit_code = code,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    let mut logs = docker.logs(
+        container_name,
+        Some(bollard::query_parameters::LogsOptions {
+            stdout: true,
+            stderr: true,
+            ..Default::default()
+        }),
+    );
+
+    while let Some(chunk) = logs.next().await {
+        match chunk? {
+            bollard::container::LogOutput::StdErr { message } => {
+                stderr.push_str(&String::from_utf8_lossy(&message))
+            }
+            other => stdout.push_str(&String::from_utf8_lossy(&other.into_bytes())),
+        }
+    }
+
+    Ok(ContainerOutput {
+        exit_code,
+        stdout,
+        stderr,
+    })
+}
+
+pub async fn remove_container_if_exists(
+    docker: &bollard::Docker,
+    container_name: &str,
+) -> Result<()> {
+    match docker
+        .remove_container(
+            container_name,
+            Some(bollard::query_parameters::RemoveContainerOptions {
+                force: true,
+                ..Default::default()
+            }),
+        )
+        .await
+    {
+        Ok(_)
+        | Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,222 @@
+use std::time::{Duration, Instant};
+
+use bollard::models;
+
+use crate::{config, docker, helper, prelude::*};
+
+/// Seconds after which a single probe attempt is considered failed
+const PROBE_ATTEMPT_TIMEOUT: u64 = 5;
+
+/// Consecutive failures after the start period before docker marks a container unhealthy
+const HEALTHCHECK_RETRIES: i64 = 3;
+
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Printed by probes whose tool is missing in the image,
+/// so the wait can fail right away instead of running into the timeout
+const MISSING_PROBE_TOOL: &str = "dploy: health check tool not found in the image";
+
+#[derive(Debug, Clone)]
+pub enum HealthProbe {
+    /// HTTP request to the given port
+    Http { port: u16, path: String },
+
+    /// TCP connection to the given port
+    Tcp { port: u16 },
+
+    /// Shell command executed inside the container
+    Command(String),
+}
+
+impl HealthProbe {
+    /// Shell command performing the probe against the given host
+    pub fn to_shell_command(&self, host: &str) -> String {
+        match self {
+            HealthProbe::Http { port, path } => {
+                let url = format!("http://{host}:{port}{path}");
+
+                format!(
+                    "if command -v wget > /dev/null; then wget -q -O /dev/null -T {PROBE_ATTEMPT_TIMEOUT} '{url}'; \
+                    elif command -v curl > /dev/null; then curl -fsS -o /dev/null -m {PROBE_ATTEMPT_TIMEOUT} '{url}'; \
+                    else echo '{MISSING_PROBE_TOOL}: http checks need wget or curl'; exit 1; fi"
+                )
+            }
+            HealthProbe::Tcp { port } => format!(
+                "if command -v nc > /dev/null; then nc -z -w {PROBE_ATTEMPT_TIMEOUT} {host} {port}; \
+                else echo '{MISSING_PROBE_TOOL}: tcp checks need nc'; exit 1; fi"
+            ),
+            HealthProbe::Command(command) => command.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Healthcheck {
+    probe: HealthProbe,
+    timeout: Duration,
+    interval: Duration,
+}
+
+impl Healthcheck {
+    pub fn new(probe: HealthProbe, timeout: Duration, interval: Duration) -> Self {
+        Self {
+            probe,
+            timeout,
+            interval,
+        }
+    }
+
+    pub fn from_config(config: &config::HealthcheckConfig) -> Result<Self> {
+        let probe = match (config.http(), config.tcp(), config.command()) {
+            (Some(path), None, None) => {
+                let Some(port) = config.port() else {
+                    bail!("[healthcheck] with `http` also requires `port`");
+                };
+
+                // the path ends up in a single-quoted shell argument
+                let path = path.replace('\'', "%27");
+                let path = if path.starts_with('/') {
+                    path
+                } else {
+                    format!("/{path}")
+                };
+
+                HealthProbe::Http { port, path }
+            }
+            (None, Some(port), None) => HealthProbe::Tcp { port },
+            (None, None, Some(command)) => HealthProbe::Command(command.to_owned()),
+            _ => bail!("[healthcheck] must have exactly one of `http`, `tcp` or `command`"),
+        };
+
+        Ok(Self::new(
+            probe,
+            Duration::from_secs(config.timeout()),
+            Duration::from_secs(config.interval()),
+        ))
+    }
+
+    pub fn probe(&self) -> &HealthProbe {
+        &self.probe
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Docker healthcheck running the probe inside the container.
+    /// Failures during `timeout` after the start don't count as unhealthy
+    pub fn to_health_config(&self) -> models::HealthConfig {
+        models::HealthConfig {
+            test: Some(vec![
+                "CMD-SHELL".to_owned(),
+                self.probe.to_shell_command("127.0.0.1"),
+            ]),
+            interval: Some(as_nanos(self.interval)),
+            timeout: Some(as_nanos(Duration::from_secs(PROBE_ATTEMPT_TIMEOUT))),
+            retries: Some(HEALTHCHECK_RETRIES),
+            start_period: Some(as_nanos(self.timeout)),
+            ..Default::default()
+        }
+    }
+}
+
+fn as_nanos(duration: Duration) -> i64 {
+    duration.as_nanos().try_into().unwrap_or(i64::MAX)
+}
+
+/// Waits until docker reports the container as healthy.
+/// Containers without a healthcheck are considered healthy right away
+pub async fn wait_container_healthy(
+    docker: &bollard::Docker,
+    container_name: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let Some(container) = docker::inspect_container(docker, container_name).await? else {
+            bail!("{container_name} does not exist");
+        };
+
+        let Some(health) = container.state.and_then(|state| state.health) else {
+            return Ok(());
+        };
+
+        if matches!(
+            health.status,
+            Some(models::HealthStatusEnum::HEALTHY | models::HealthStatusEnum::NONE)
+        ) {
+            return Ok(());
+        }
+
+        let last_output = health
+            .log
+            .and_then(|log| log.into_iter().last())
+            .and_then(|result| result.output)
+            .unwrap_or_default();
+
+        if let Some(missing) = last_output.trim().strip_prefix(MISSING_PROBE_TOOL) {
+            bail!(
+                "{container_name} can't run its health check, {}. Install it in the image or use a `command` health check",
+                missing.trim_start_matches(':').trim(),
+            );
+        }
+
+        if Instant::now() >= deadline {
+            bail!(
+                "{container_name} did not become healthy within {}s. Last health check output:\n{}",
+                timeout.as_secs(),
+                last_output.trim(),
+            );
+        }
+
+        tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+    }
+}
+
+/// Polls the probe from the dploy network until it passes or the timeout is reached.
+/// Used for containers without a docker healthcheck.
+/// `host` is the name the container is reachable by inside the dploy network
+pub async fn wait_probe(
+    docker: &bollard::Docker,
+    container_name: &str,
+    host: &str,
+    healthcheck: &Healthcheck,
+) -> Result<()> {
+    let deadline = Instant::now() + healthcheck.timeout();
+
+    loop {
+        let output = match healthcheck.probe() {
+            HealthProbe::Command(command) => {
+                docker::exec_command_output(docker, container_name, command).await?
+            }
+            probe => {
+                let script = probe.to_shell_command(host);
+                helper::run_network_script(docker, container_name, &script).await?
+            }
+        };
+
+        if output.exit_code == 0 {
+            return Ok(());
+        }
+
+        if Instant::now() + healthcheck.interval() > deadline {
+            let details = [output.stderr.trim(), output.stdout.trim()]
+                .into_iter()
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            bail!(
+                "Health check of {container_name} did not pass within {}s\n{details}",
+                healthcheck.timeout().as_secs(),
+            );
+        }
+
+        tokio::time::sleep(healthcheck.interval()).await;
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{constants, context, docker, network, prelude::*, services::ServiceKind};
+
+const HELPER_IMAGE_NAME: &str = "busybox";
+const HELPER_IMAGE_TAG: &str = "1.36";
+
+pub const VOLUMES_INNER_DIR: &str = "/volumes";
+
+/// Helper inspecting the volumes of all projects, so it belongs to none of them
+const LS_HELPER_NAME: &str = "dploy-singleton_ls_helper";
+
+/// Runs a shell script in a short-lived container with the volumes
+/// directory of the service mounted at `VOLUMES_INNER_DIR`.
+/// This works the same way for local and remote docker hosts
+pub async fn run_volumes_script(
+    docker: &bollard::Docker,
+    context: &context::Context,
+    service_kind: ServiceKind,
+    script: &str,
+    env: &[(&str, &str)],
+) -> Result<docker::ContainerOutput> {
+    let volumes_dir = context.volumes_dir_of(service_kind);
+    let helper_name = format!("{}_helper", context.container_name_of(service_kind));
+
+    run_mounted_script(docker, &helper_name, &volumes_dir, script, env).await
+}
+
+/// Deletes the volumes directory of the container including generated secrets.
+/// Returns whether the directory existed
+pub async fn remove_volumes_dir(
+    docker: &bollard::Docker,
+    context: &context::Context,
+    container_name: &str,
+) -> Result<bool> {
+    let volumes_dir = context.volumes_dir_of_container(container_name);
+
+    // the parent is mounted, so the directory itself can be removed
+    let (Some(parent_dir), Some(dir_name)) = (volumes_dir.parent(), volumes_dir.file_name()) else {
+        bail!("Invalid volumes directory {}", volumes_dir.display());
+    };
+
+    let helper_name = format!("{container_name}_helper");
+
+    let output = run_mounted_script(
+        docker,
+        &helper_name,
+        parent_dir,
+        r#"if [ -d "$DPLOY_VOLUMES_DIR/$DIR_NAME" ]; then rm -rf "$DPLOY_VOLUMES_DIR/$DIR_NAME" && echo removed; fi"#,
+        &[("DIR_NAME", &dir_name.to_string_lossy())],
+    )
+    .await?;
+
+    if output.exit_code != 0 {
+        bail!(
+            "Could not remove {}: {}",
+            volumes_dir.display(),
+            output.stderr.trim()
+        );
+    }
+
+    Ok(output.stdout.trim() == "removed")
+}
+
+/// Disk usage in bytes of the volumes directories of all containers on the host,
+/// keyed by container name
+pub async fn volumes_disk_usage(docker: &bollard::Docker) -> Result<HashMap<String, u64>> {
+    let volumes_dir = Path::new(constants::DPLOY_DIR).join("volumes");
+
+    let output = run_mounted_script(
+        docker,
+        LS_HELPER_NAME,
+        &volumes_dir,
+        r#"for dir in "$DPLOY_VOLUMES_DIR"/*; do if [ -d "$dir" ]; then du -sk "$dir"; fi; done"#,
+        &[],
+    )
+    .await?;
+
+    if output.exit_code != 0 {
+        bail!(
+            "Could not get disk usage of {}: {}",
+            volumes_dir.display(),
+            output.stderr.trim()
+        );
+    }
+
+    Ok(output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let (size, dir) = line.split_once(char::is_whitespace)?;
+            let size = size.parse::<u64>().ok()?;
+            let name = Path::new(dir.trim()).file_name()?.to_string_lossy();
+
+            Some((name.to_string(), size * 1024))
+        })
+        .collect())
+}
+
+async fn run_mounted_script(
+    docker: &bollard::Docker,
+    helper_name: &str,
+    volumes_dir: &Path,
+    script: &str,
+    env: &[(&str, &str)],
+) -> Result<docker::ContainerOutput> {
+    docker::ensure_image(docker, HELPER_IMAGE_NAME, HELPER_IMAGE_TAG).await?;
+
+    let config = bollard::models::ContainerCreateBody {
+        image: Some(format!("{HELPER_IMAGE_NAME}:{HELPER_IMAGE_TAG}")),
+
+        cmd: Some(["sh", "-c", script].into_iter().map(String::from).collect()),
+
+        env: Some(
+            std::iter::once(format!("DPLOY_VOLUMES_DIR={VOLUMES_INNER_DIR}"))
+                .chain(env.iter().map(|(key, value)| format!("{key}={value}")))
+                .collect(),
+        ),
+
+        host_config: Some(bollard::models::HostConfig {
+            mounts: Some(vec![bollard::models::Mount {
+                source: Some(volumes_dir.to_string_lossy().to_string()),
+                target: Some(VOLUMES_INNER_DIR.to_owned()),
+
+                bind_options: Some(bollard::models::MountBindOptions {
+                    create_mountpoint: Some(true),
+                    ..Default::default()
+                }),
+
+                typ: Some(bollard::models::MountTypeEnum::BIND),
+
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }),
+
+        ..Default::default()
+    };
+
+    docker::run_container_to_completion(docker, helper_name, config).await
+}
+
+/// Runs a shell script in a short-lived container attached to the dploy network,
+/// so it can reach services by their container names
+pub async fn run_network_script(
+    docker: &bollard::Docker,
+    container_name: &str,
+    script: &str,
+) -> Result<docker::ContainerOutput> {
+    docker::ensure_image(docker, HELPER_IMAGE_NAME, HELPER_IMAGE_TAG).await?;
+
+    let config = bollard::models::ContainerCreateBody {
+        image: Some(format!("{HELPER_IMAGE_NAME}:{HELPER_IMAGE_TAG}")),
+
+        cmd: Some(["sh", "-c", script].into_iter().map(String::from).collect()),
+
+        networking_config: Some(bollard::models::NetworkingConfig {
+            endpoints_config: Some(HashMap::from([(
+                network::DPLOY_NETWORK.to_owned(),
+                bollard::models::EndpointSettings::default(),
+            )])),
+        }),
+
+        ..Default::default()
+    };
+
+    let helper_name = format!("{container_name}_probe");
+
+    docker::run_container_to_completion(docker, &helper_name, config).await
+}
@@ -0,0 +1,95 @@
+use crate::{
+    config::{HookConfig, HookPhase, HookService},
+    context, docker,
+    prelude::*,
+    presentation,
+    services::{self, ServiceKind},
+};
+
+impl From<HookService> for ServiceKind {
+    fn from(service: HookService) -> Self {
+        match service {
+            HookService::App => ServiceKind::App,
+            HookService::Postgres => ServiceKind::Postgres,
+            HookService::Keydb => ServiceKind::Keydb,
+        }
+    }
+}
+
+/// Runs all hooks of the phase in the order they are configured.
+/// `image_tag` is the app image used by hooks without a service.
+/// Fails on the first hook exiting with a non-zero code
+pub async fn run(
+    context: &context::Context,
+    docker: &bollard::Docker,
+    services: &services::Services,
+    phase: HookPhase,
+    image_tag: &str,
+) -> Result<()> {
+    let hooks = context
+        .app_config()
+        .hooks(context.override_context())
+        .iter()
+        .filter(|hook| hook.phase == phase);
+
+    for hook in hooks {
+        let label = phase.to_string();
+
+        let output = match hook.service {
+            None => {
+                let Some(app_service) = services.app() else {
+                    // e.g. dev mode, where the app image is not built
+                    presentation::print_hook_skipped(&label, &hook.command);
+                    continue;
+                };
+
+                presentation::print_hook_running(&label, &hook.command);
+                run_in_app_image(context, docker, app_service, hook, image_tag).await?
+            }
+            Some(service) => {
+                let container_name = context.container_name_of(service.into());
+
+                if !docker::check_container_running(docker, &container_name).await? {
+                    // there is nothing to clean up in a service which is not running
+                    if phase == HookPhase::PreStop {
+                        presentation::print_hook_skipped(&label, &hook.command);
+                        continue;
+                    }
+
+                    bail!(
+                        "Could not run {phase} hook `{}`: {container_name} is not running",
+                        hook.command
+                    );
+                }
+
+                presentation::print_hook_running(&label, &hook.command);
+                docker::exec_command_output(docker, &container_name, &hook.command).await?
+            }
+        };
+
+        presentation::print_hook_output(&output.stdout, &output.stderr);
+
+        if output.exit_code != 0 {
+            bail!(
+                "{phase} hook `{}` failed with exit code {}",
+                hook.command,
+                output.exit_code
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_in_app_image(
+    context: &context::Context,
+    docker: &bollard::Docker,
+    app_service: &services::app::AppService,
+    hook: &HookConfig,
+    image_tag: &str,
+) -> Result<docker::ContainerOutput> {
+    let config = app_service.to_hook_container_config(context, image_tag, &hook.command)?;
+    let container_name = format!("{}_hook", context.container_name_of(ServiceKind::App));
+
+    docker::run_container_to_completion(docker, &container_name, config).await
+}
@@ -13,9 +13,15 @@ mod config;
 mod constants;
 mod context;
 mod docker;
+mod health;
+mod helper;
+mod hooks;
 mod network;
 mod prelude;
 mod presentation;
+mod readiness;
+mod releases;
+mod secrets;
 mod services;
 mod ssh;
 mod utils;
@@ -23,7 +29,8 @@ mod utils;
 #[tokio::main]
 async fn main() -> Result<()> {
     match run_cli().await {
-        Ok(_) => Ok(()),
+        Ok(0) => Ok(()),
+        Ok(exit_code) => std::process::exit(exit_code),
         Err(error) => {
             eprintln!("{}", error);
             std::process::exit(1);
@@ -31,9 +38,32 @@ async fn main() -> Result<()> {
     }
 }
 
-async fn run_cli() -> Result<()> {
+/// Returns the exit code of the CLI
+async fn run_cli() -> Result<i32> {
     let args = cli::Args::try_parse()?;
 
+    // host commands cover all projects, so they don't read the config
+    if let cli::Command::Ls { host } | cli::Command::Gc { host, .. } = args.command() {
+        presentation::print_cli_info();
+
+        let credentials = host.ssh_credentials();
+        let (docker, session) = docker::get_host_docker_client(credentials.as_ref()).await?;
+
+        match args.command() {
+            cli::Command::Gc { stale, dry_run, .. } => {
+                let args = commands::gc::GcArgs::new(*stale, *dry_run);
+                commands::gc::gc(&docker, &args).await?;
+            }
+            _ => commands::ls::ls(&docker).await?,
+        }
+
+        if let Some(session) = session {
+            session.close().await?;
+        }
+
+        return Ok(0);
+    }
+
     let config_path = path::PathBuf::from(&args.config);
 
     let Some(cwd) = config_path.parent() else {
@@ -41,11 +71,14 @@ async fn run_cli() -> Result<()> {
     };
     std::env::set_current_dir(cwd)?;
 
-    presentation::print_cli_info();
-
     let namespace = args.namespace();
-    if namespace != constants::DEFAULT_NAMESPACE {
-        presentation::print_namespace_info(namespace);
+
+    if !args.is_json_output() {
+        presentation::print_cli_info();
+
+        if namespace != constants::DEFAULT_NAMESPACE {
+            presentation::print_namespace_info(namespace);
+        }
     }
 
     let override_context = config::OverrideContext {
@@ -68,7 +101,9 @@ async fn run_cli() -> Result<()> {
     let app_config: config::AppConfig = toml::from_str(&file_contents)?;
 
     let context = Arc::new(context::Context::new(args, app_config, override_context));
-    let services = services::Services::from_context(&context);
+
+    // exec passes through the exit code of the command
+    let mut exit_code = 0;
 
     match context.args().command() {
         cli::Command::Dev { command, .. } => {
@@ -76,10 +111,20 @@ async fn run_cli() -> Result<()> {
 
             match command {
                 None => {
+                    let services = create_services(&context, &docker).await?;
                     commands::deploy::deploy(&context, &docker, &services).await?;
                 }
                 Some(cli::DevCommand::Stop) => {
-                    commands::stop::stop(&context, &docker, &services).await?;
+                    let services = create_services(&context, &docker).await?;
+                    let args = commands::stop::StopArgs::default();
+                    commands::stop::stop(&context, &docker, &services, &args).await?;
+                }
+                Some(cli::DevCommand::Status { json }) => {
+                    commands::status::status(&context, &docker, *json).await?;
+                }
+                Some(cli::DevCommand::Destroy { volumes, yes }) => {
+                    let args = commands::destroy::DestroyArgs::new(*volumes, *yes);
+                    commands::destroy::destroy(&context, &docker, &args).await?;
                 }
                 Some(cli::DevCommand::Logs { tail, service, .. }) => {
                     commands::logs::logs(
@@ -94,7 +139,7 @@ async fn run_cli() -> Result<()> {
                     service, command, ..
                 }) => {
                     let args = commands::exec::ExecArgs::new((*service).into(), command.clone());
-                    commands::exec::exec(&context, &docker, &args).await?;
+                    exit_code = commands::exec::exec(&context, &docker, &args).await?;
                 }
             }
         }
@@ -103,8 +148,10 @@ async fn run_cli() -> Result<()> {
         cli::Command::Run {
             command: None,
             watch: true,
+            ..
         } => {
             let docker = docker::get_default_docker_client().await?;
+            let services = create_services(&context, &docker).await?;
             commands::deploy::deploy_watch(
                 Arc::clone(&context),
                 Arc::new(docker),
@@ -124,16 +171,26 @@ async fn run_cli() -> Result<()> {
 
             match command {
                 None => {
+                    let services = create_services(&context, &docker).await?;
                     commands::deploy::deploy(&context, &docker, &services).await?;
                 }
-                Some(cli::RunCommand::Stop) => {
-                    commands::stop::stop(&context, &docker, &services).await?;
+                Some(cli::RunCommand::Stop { service }) => {
+                    let services = create_services(&context, &docker).await?;
+                    let args = commands::stop::StopArgs::new(service.clone());
+                    commands::stop::stop(&context, &docker, &services, &args).await?;
+                }
+                Some(cli::RunCommand::Status { json }) => {
+                    commands::status::status(&context, &docker, *json).await?;
+                }
+                Some(cli::RunCommand::Destroy { volumes, yes }) => {
+                    let args = commands::destroy::DestroyArgs::new(*volumes, *yes);
+                    commands::destroy::destroy(&context, &docker, &args).await?;
                 }
                 Some(cli::RunCommand::Logs { tail, service, .. }) => {
                     commands::logs::logs(
                         Arc::clone(&context),
                         Arc::new(docker),
-                        (*service).into(),
+                        service.clone(),
                         *tail,
                     )
                     .await?;
@@ -141,8 +198,8 @@ async fn run_cli() -> Result<()> {
                 Some(cli::RunCommand::Exec {
                     service, command, ..
                 }) => {
-                    let args = commands::exec::ExecArgs::new((*service).into(), command.clone());
-                    commands::exec::exec(&context, &docker, &args).await?;
+                    let args = commands::exec::ExecArgs::new(service.clone(), command.clone());
+                    exit_code = commands::exec::exec(&context, &docker, &args).await?;
                 }
             }
         }
@@ -153,6 +210,7 @@ async fn run_cli() -> Result<()> {
             ..
         } => {
             let (docker, session) = docker::get_docker_client_with_session(&context).await?;
+            let services = create_services(&context, &docker).await?;
             commands::deploy::deploy_watch(
                 Arc::clone(&context),
                 Arc::new(docker),
@@ -173,16 +231,26 @@ async fn run_cli() -> Result<()> {
 
             match command {
                 None => {
+                    let services = create_services(&context, &docker).await?;
                     commands::deploy::deploy(&context, &docker, &services).await?;
                 }
-                Some(cli::DeployCommand::Stop) => {
-                    commands::stop::stop(&context, &docker, &services).await?;
+                Some(cli::DeployCommand::Stop { service }) => {
+                    let services = create_services(&context, &docker).await?;
+                    let args = commands::stop::StopArgs::new(service.clone());
+                    commands::stop::stop(&context, &docker, &services, &args).await?;
+                }
+                Some(cli::DeployCommand::Status { json }) => {
+                    commands::status::status(&context, &docker, *json).await?;
+                }
+                Some(cli::DeployCommand::Destroy { volumes, yes }) => {
+                    let args = commands::destroy::DestroyArgs::new(*volumes, *yes);
+                    commands::destroy::destroy(&context, &docker, &args).await?;
                 }
                 Some(cli::DeployCommand::Logs { tail, service, .. }) => {
                     commands::logs::logs(
                         Arc::clone(&context),
                         Arc::new(docker),
-                        (*service).into(),
+                        service.clone(),
                         *tail,
                     )
                     .await?;
@@ -190,14 +258,46 @@ async fn run_cli() -> Result<()> {
                 Some(cli::DeployCommand::Exec {
                     service, command, ..
                 }) => {
-                    let args = commands::exec::ExecArgs::new((*service).into(), command.clone());
-                    commands::exec::exec(&context, &docker, &args).await?;
+                    let args = commands::exec::ExecArgs::new(service.clone(), command.clone());
+                    exit_code = commands::exec::exec(&context, &docker, &args).await?;
+                }
+                Some(cli::DeployCommand::Rollback { release }) => {
+                    let services = create_services(&context, &docker).await?;
+                    commands::deploy::rollback(&context, &docker, &services, release.as_deref())
+                        .await?;
                 }
+                Some(cli::DeployCommand::Releases) => {
+                    commands::releases::releases(&context, &docker).await?;
+                }
+                Some(cli::DeployCommand::Proxy { command }) => match command {
+                    cli::ProxyCommand::Status => {
+                        commands::proxy::status(&context, &docker).await?;
+                    }
+                    cli::ProxyCommand::Restart => {
+                        commands::proxy::restart(&context, &docker).await?;
+                    }
+                    cli::ProxyCommand::Remove => {
+                        commands::proxy::remove(&context, &docker).await?;
+                    }
+                },
             }
 
             session.close().await?;
         }
+
+        cli::Command::Ls { .. } | cli::Command::Gc { .. } => {
+            unreachable!("host commands are handled before the config is read")
+        }
     }
 
-    Ok(())
+    Ok(exit_code.try_into().unwrap_or(1))
+}
+
+async fn create_services(
+    context: &context::Context,
+    docker: &bollard::Docker,
+) -> Result<services::Services> {
+    commands::deploy::load_env_file(context);
+
+    services::Services::from_context(context, docker).await
 }
@@ -7,8 +7,8 @@ pub const DPLOY_NETWORK: &str = "dploy_default";
 
 pub async fn create_dploy_network(docker: &Docker) -> Result<()> {
     let result = docker
-        .create_network(bollard::network::CreateNetworkOptions {
-            name: DPLOY_NETWORK,
+        .create_network(bollard::models::NetworkCreateRequest {
+            name: DPLOY_NETWORK.to_owned(),
             ..Default::default()
         })
         .await;
@@ -22,3 +22,38 @@ pub async fn create_dploy_network(docker: &Docker) -> Result<()> {
         Err(error) => Err(error.into()),
     }
 }
+
+/// The network is shared by all projects, so it's removed only without attached containers.
+/// Returns whether it was removed
+pub async fn remove_dploy_network_if_unused(docker: &Docker) -> Result<bool> {
+    let network = match docker
+        .inspect_network(
+            DPLOY_NETWORK,
+            None::<bollard::query_parameters::InspectNetworkOptions>,
+        )
+        .await
+    {
+        Ok(network) => network,
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => return Ok(false),
+        Err(error) => return Err(error.into()),
+    };
+
+    if network
+        .containers
+        .is_some_and(|containers| !containers.is_empty())
+    {
+        return Ok(false);
+    }
+
+    match docker.remove_network(DPLOY_NETWORK).await {
+        Ok(_) => Ok(true),
+        // a container was attached in the meantime
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: CONFLICT_STATUS_CODE,
+            ..
+        }) => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
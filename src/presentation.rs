@@ -1,6 +1,16 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use console::style;
 
-use crate::services::ServiceKind;
+use crate::{
+    commands::{ls::ListedNamespace, status::ServiceStatus},
+    releases::Release,
+    services::ServiceKind,
+};
 
 #[inline]
 pub fn print_cli_info() {
@@ -52,6 +62,227 @@ pub fn print_logs_count(service_name: &str, count: u64, is_follow: bool) {
     println!();
 }
 
+pub fn print_releases(label: &str, releases: &[Release], current_image_id: Option<&str>) {
+    if releases.is_empty() {
+        println!(
+            "{}",
+            style(format!("No releases of {label} found")).yellow()
+        );
+        return;
+    }
+
+    println!("{}", style(format!("\nReleases of {label}:\n")).cyan());
+
+    for release in releases {
+        let is_current = current_image_id == Some(release.image_id());
+        let created = chrono::DateTime::from_timestamp(release.created(), 0)
+            .map(|created| created.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        let size = format!("{:.1} MB", release.size() as f64 / 1_000_000.0);
+
+        if is_current {
+            println!(
+                "{} {}  {}  {}",
+                style("*").green(),
+                style(release.id()).green().bold(),
+                created,
+                style(size).dim()
+            );
+        } else {
+            println!("  {}  {}  {}", release.id(), created, style(size).dim());
+        }
+    }
+
+    println!();
+}
+
+pub fn print_proxy_status(
+    name: &str,
+    state: Option<&str>,
+    image: Option<&str>,
+    site_files: &[String],
+) {
+    let Some(state) = state else {
+        println!("{} does not exist", style(name).cyan());
+        return;
+    };
+
+    println!("{}", style("\nProxy:\n").cyan());
+    println!("Container: {}", style(name).cyan());
+    println!("State: {}", style(state).bold());
+
+    if let Some(image) = image {
+        println!("Image: {}", image);
+    }
+
+    if site_files.is_empty() {
+        println!("Sites: {}", style("none").dim());
+    } else {
+        println!("Sites:");
+
+        for site_file in site_files {
+            println!("  {}", site_file);
+        }
+    }
+
+    println!();
+}
+
+/// Printed as is, so the output can be piped into other tools
+pub fn print_status_json(json: &str) {
+    println!("{json}");
+}
+
+pub fn print_status(statuses: &[ServiceStatus]) {
+    println!("{}", style("\nStatus:\n").cyan());
+
+    for status in statuses {
+        let Some(state) = status.state() else {
+            println!(
+                "{}  {}",
+                style(status.container()).cyan(),
+                style("not created").dim()
+            );
+            continue;
+        };
+
+        let state = match status.health() {
+            Some(health) => format!("{state} ({health})"),
+            None => state.to_owned(),
+        };
+        let state = if status.uptime().is_some() {
+            style(state).green()
+        } else {
+            style(state).yellow()
+        };
+
+        print!("{}  {}", style(status.container()).cyan(), state);
+
+        if let Some(uptime) = status.uptime() {
+            print!("  up {}", format_duration(uptime));
+        }
+
+        println!("  restarts {}", status.restart_count().unwrap_or_default());
+
+        if let Some(release) = status.release() {
+            println!("  release: {}", release);
+        }
+
+        if let Some(image_id) = status.image_id() {
+            println!("  image: {}", style(image_id).dim());
+        }
+
+        if !status.ports().is_empty() {
+            println!("  ports: {}", status.ports().join(", "));
+        }
+
+        if !status.domains().is_empty() {
+            println!("  domains: {}", status.domains().join(", "));
+        }
+    }
+
+    println!();
+}
+
+pub fn print_namespaces(namespaces: &[ListedNamespace]) {
+    if namespaces.is_empty() {
+        println!("{}", style("No projects found").yellow());
+        return;
+    }
+
+    for namespace in namespaces {
+        println!(
+            "\n{} {}  {}",
+            style(namespace.project()).cyan().bold(),
+            style(namespace.namespace()).cyan(),
+            style(format!(
+                "volumes {:.1} MB",
+                namespace.volumes_size() as f64 / 1_000_000.0
+            ))
+            .dim()
+        );
+
+        for container in namespace.containers() {
+            let status = if container.state() == "running" {
+                style(container.status()).green()
+            } else {
+                style(container.status()).yellow()
+            };
+
+            println!(
+                "  {}  {}  {}  {}",
+                container.kind(),
+                container.name(),
+                status,
+                style(format!("v{}", container.version())).dim()
+            );
+        }
+    }
+
+    println!();
+}
+
+fn format_duration(seconds: i64) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{seconds}s"),
+        (0, 0, _) => format!("{minutes}m"),
+        (0, _, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h"),
+    }
+}
+
+#[inline]
+pub fn print_gc_removed(kind: &str, name: &str, dry_run: bool) {
+    let action = if dry_run {
+        style("Would remove").yellow()
+    } else {
+        style("Removed").green()
+    };
+
+    println!("[{}] {} {}", style(kind).cyan(), action, name);
+}
+
+#[inline]
+pub fn print_gc_namespace_stale(project: &str, namespace: &str, stopped_for: Duration) {
+    println!(
+        "\n{} {} stopped {} ago",
+        style(project).cyan().bold(),
+        style(namespace).cyan(),
+        format_duration(stopped_for.as_secs().try_into().unwrap_or(i64::MAX))
+    );
+}
+
+#[inline]
+pub fn print_gc_nothing_to_remove() {
+    println!("{}", style("Nothing to remove").green());
+}
+
+#[inline]
+pub fn print_destroy_removed(kind: &str, name: &str) {
+    println!(
+        "[{}] {} {}",
+        style(kind).cyan(),
+        style("Removed").green(),
+        name
+    );
+}
+
+pub fn print_destroy_volumes_confirmation(volumes_dirs: &[PathBuf]) {
+    println!(
+        "{}",
+        style("\nThe following directories will be deleted permanently:\n").red()
+    );
+
+    for volumes_dir in volumes_dirs {
+        println!("  {}", volumes_dir.display());
+    }
+
+    print!("\nContinue? [y/N] ");
+    std::io::stdout().flush().ok();
+}
+
 #[inline]
 pub fn print_namespace_info(namespace: &str) {
     println!(
@@ -61,6 +292,57 @@ pub fn print_namespace_info(namespace: &str) {
     )
 }
 
+#[inline]
+pub fn print_dependency_ready(label: &str, elapsed: Duration) {
+    println!(
+        "[{}] {} after {}s",
+        style(label).cyan(),
+        style("Ready").green(),
+        elapsed.as_secs()
+    );
+}
+
+#[inline]
+pub fn print_dependency_not_ready(label: &str, elapsed: Duration, reason: &str) {
+    println!(
+        "[{}] {} ({}s): {}",
+        style(label).cyan(),
+        style("Not ready yet").yellow(),
+        elapsed.as_secs(),
+        style(reason).dim()
+    );
+}
+
+#[inline]
+pub fn print_hook_running(label: &str, command: &str) {
+    println!(
+        "[{}] {} {}",
+        style(label).cyan(),
+        style("Running hook").cyan(),
+        style(command).bold()
+    );
+}
+
+#[inline]
+pub fn print_hook_skipped(label: &str, command: &str) {
+    println!(
+        "[{}] {} {}",
+        style(label).cyan(),
+        style("Skipping hook, its service is not running in this mode").yellow(),
+        style(command).bold()
+    );
+}
+
+pub fn print_hook_output(stdout: &str, stderr: &str) {
+    if !stdout.trim().is_empty() {
+        println!("{}", stdout.trim_end());
+    }
+
+    if !stderr.trim().is_empty() {
+        eprintln!("{}", style(stderr.trim_end()).red());
+    }
+}
+
 #[inline]
 pub fn print_image_building(label: &str, dockerfile: &str) {
     println!(
@@ -70,6 +352,131 @@ pub fn print_image_building(label: &str, dockerfile: &str) {
     );
 }
 
+#[inline]
+pub fn print_image_shared(label: &str, built_by: &str) {
+    println!(
+        "[{}] {} {}",
+        style(label).cyan(),
+        style("Using the image built for").green(),
+        style(built_by).cyan().bold()
+    );
+}
+
+#[inline]
+pub fn print_build_line(line: &str) {
+    println!("{}", style(line).dim());
+}
+
+/// Redraws the step counter in place, only when the output is a terminal
+pub fn print_build_step(label: &str, counter: &str) {
+    if !console::Term::stdout().is_term() {
+        return;
+    }
+
+    print!("\r[{}] {}", style(label).cyan(), counter);
+    std::io::stdout().flush().ok();
+}
+
+#[inline]
+pub fn print_build_steps_done() {
+    if console::Term::stdout().is_term() {
+        println!();
+    }
+}
+
+pub fn print_build_error(label: &str, step: Option<&str>, error: &str) {
+    eprintln!(
+        "[{}] {}",
+        style(label).cyan(),
+        style("Build failed").red().bold()
+    );
+
+    if let Some(step) = step {
+        eprintln!("{}", style(step).red());
+    }
+
+    eprintln!("{}", style(error.trim_end()).red());
+}
+
+#[inline]
+pub fn print_build_log_saved(path: &str) {
+    eprintln!("Full build log: {}", style(path).cyan());
+}
+
+#[inline]
+pub fn print_build_context_file(entry_path: &Path, archive_path: &Path) {
+    println!(
+        "{} {} {}",
+        style(entry_path.display()).dim(),
+        style("->").dim(),
+        archive_path.display()
+    );
+}
+
+#[inline]
+pub fn print_image_context_uploading(label: &str, files_count: usize, size: u64) {
+    println!(
+        "[{}] Uploading context: {} files, {}",
+        style(label).cyan(),
+        files_count,
+        style(format_megabytes(size)).dim()
+    );
+}
+
+#[inline]
+pub fn print_image_pulling(label: &str, image: &str) {
+    println!(
+        "[{}] Pulling image {}",
+        style(label).cyan(),
+        style(image).cyan().bold()
+    );
+}
+
+/// Redraws a progress bar in place, only when the output is a terminal
+pub fn print_image_upload_progress(label: &str, uploaded: u64, total: u64) {
+    const WIDTH: u64 = 30;
+
+    if !console::Term::stdout().is_term() {
+        return;
+    }
+
+    let filled = (uploaded * WIDTH)
+        .checked_div(total)
+        .unwrap_or(WIDTH)
+        .min(WIDTH);
+
+    print!(
+        "\r[{}] Uploading image [{}{}] {} / {}",
+        style(label).cyan(),
+        "=".repeat(filled as usize),
+        " ".repeat((WIDTH - filled) as usize),
+        format_megabytes(uploaded),
+        format_megabytes(total)
+    );
+    std::io::stdout().flush().ok();
+}
+
+#[inline]
+pub fn print_image_upload_progress_done() {
+    if console::Term::stdout().is_term() {
+        println!();
+    }
+}
+
+#[inline]
+pub fn print_image_uploading(label: &str, size: u64) {
+    println!(
+        "[{}] {} {}",
+        style(label).cyan(),
+        style("Uploading image to the server").cyan(),
+        style(format_megabytes(size)).dim()
+    );
+}
+
+fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
 macro_rules! generate_println {
     ($($fn_name:ident($message:expr)),+ $(,)? ) => {
         $(
@@ -95,6 +502,7 @@ macro_rules! generate_println_with_label {
 generate_println! {
     print_dependencies_starting(style("Starting dependencies").cyan()),
     print_dependencies_stopping(style("Stopping dependencies").cyan()),
+    print_dependencies_readiness_checking(style("Checking dependencies readiness").cyan()),
     print_env_file_generating(style("Generating env file").cyan()),
     print_env_file_loaded(style("Loaded env file").green()),
     print_env_file_failed_to_load(style("Failed to load env file").yellow()),
@@ -106,6 +514,7 @@ generate_println! {
     print_ctrlc_received(style("\n\nReceived escape sequence. Please wait until current tasks are finished\n").red()),
     print_ctrlc_started(style("\nStopping services because of escape sequence...\n").red()),
     print_watch_files_changed(style("\nFiles changed. Restarting...").cyan()),
+    print_pre_up_running(style("Pre up checks running").cyan()),
     print_post_up_running(style("Post up actions running").cyan()),
     print_post_down_running(style("Post down actions running").cyan()),
 }
@@ -115,19 +524,36 @@ generate_println_with_label! {
     print_dependency_stopped(style("Stopped").green()),
     print_dependency_already_stopped(style("Already stopped").green()),
     print_dependency_success(style("Success").green()),
+    print_dependency_up_to_date(style("Up to date").green()),
+    print_dependency_health_checking(style("Waiting for health check").cyan()),
+    print_dependency_healthy(style("Healthy").green()),
+    print_dependency_readiness_waiting(style("Waiting until ready").cyan()),
     print_dependency_starting(style("Starting").cyan()),
     print_dependency_creating(style("Creating").cyan()),
     print_dependency_pulling(style("Pulling").cyan()),
     print_image_built(style("Image built").green()),
+    print_image_uploaded(style("Image uploaded").green()),
+    print_image_pulled(style("Image pulled").green()),
+    print_image_context_unchanged(style("Context unchanged, reusing the image").green()),
     print_app_container_creating(style("Creating container").cyan()),
     print_app_container_removing(style("Removing container").cyan()),
+    print_app_container_restoring(style("Restoring the previous release").yellow()),
+    print_app_container_recovering(style("Still serving traffic, taking over the container name").yellow()),
     print_app_container_starting(style("Starting container").cyan()),
+    print_app_container_health_checking(style("Waiting for health check").cyan()),
     print_app_container_success(style("Success").green()),
     print_app_container_already_stopped(style("Already stopped").green()),
     print_app_container_stopped(style("Stopped").green()),
     print_remote_host_connecting(style("Connecting").cyan()),
     print_remote_host_success(style("Success").green()),
     print_proxy_updating(style("Updating").cyan()),
+    print_proxy_restarting(style("Restarting").cyan()),
+    print_proxy_restarted(style("Restarted").green()),
+    print_proxy_removing(style("Removing").cyan()),
+    print_proxy_removed(style("Removed").green()),
+    print_proxy_site_unrouted(style("Will no longer be routed").yellow()),
     print_proxy_success(style("Success").green()),
     print_command_executing(style("Executing command\n").cyan()),
+    print_release_removed(style("Removed old release").dim()),
+    print_release_rolling_back(style("Rolling back").cyan()),
 }
@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+use crate::{context::HostPortBinding, docker, prelude::*, presentation};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+const HOST_TCP_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone)]
+pub enum ReadinessCheck {
+    /// Command executed inside the container, ready on zero exit code
+    Exec {
+        container_name: String,
+        command: String,
+    },
+
+    /// TCP connection to the port published on the host running dploy
+    HostTcp { host: String, port: u16 },
+}
+
+impl ReadinessCheck {
+    /// TCP check of the published port. Ports are published only in the
+    /// local modes, where dploy runs on the same host as docker
+    pub fn host_tcp_of(binding: &HostPortBinding) -> Option<Self> {
+        binding.host_port().map(|port| ReadinessCheck::HostTcp {
+            host: binding.host_host().to_owned(),
+            port,
+        })
+    }
+
+    async fn run(&self, docker: &bollard::Docker) -> Result<()> {
+        match self {
+            ReadinessCheck::Exec {
+                container_name,
+                command,
+            } => {
+                let output = docker::exec_command_output(docker, container_name, command).await?;
+
+                if output.exit_code != 0 {
+                    let details = [output.stderr.trim(), output.stdout.trim()]
+                        .into_iter()
+                        .find(|text| !text.is_empty())
+                        .unwrap_or_default();
+
+                    bail!("`{command}` exited with {}: {details}", output.exit_code);
+                }
+
+                Ok(())
+            }
+            ReadinessCheck::HostTcp { host, port } => {
+                tokio::time::timeout(
+                    HOST_TCP_TIMEOUT,
+                    tokio::net::TcpStream::connect((host.as_str(), *port)),
+                )
+                .await
+                .with_context(|| format!("Connection to {host}:{port} timed out"))?
+                .with_context(|| format!("Could not connect to {host}:{port}"))?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Dependency together with the checks which must pass before it's considered ready
+pub struct Readiness {
+    container_name: String,
+    checks: Vec<ReadinessCheck>,
+}
+
+impl Readiness {
+    pub fn new(container_name: String, checks: Vec<ReadinessCheck>) -> Self {
+        Self {
+            container_name,
+            checks,
+        }
+    }
+
+    pub fn container_name(&self) -> &str {
+        &self.container_name
+    }
+
+    pub fn checks(&self) -> &[ReadinessCheck] {
+        &self.checks
+    }
+}
+
+/// Polls all checks until they pass, printing progress while waiting
+pub async fn wait_ready(
+    docker: &bollard::Docker,
+    readiness: &Readiness,
+    timeout: Duration,
+) -> Result<()> {
+    let container_name = readiness.container_name();
+    let started = Instant::now();
+    let mut last_progress = started;
+
+    presentation::print_dependency_readiness_waiting(container_name);
+
+    loop {
+        let mut failure = None;
+
+        for check in readiness.checks() {
+            if let Err(error) = check.run(docker).await {
+                failure = Some(error);
+                break;
+            }
+        }
+
+        let Some(error) = failure else {
+            presentation::print_dependency_ready(container_name, started.elapsed());
+            return Ok(());
+        };
+
+        if started.elapsed() >= timeout {
+            bail!(
+                "{container_name} did not become ready within {}s. Last error: {error:#}",
+                timeout.as_secs()
+            );
+        }
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            presentation::print_dependency_not_ready(
+                container_name,
+                started.elapsed(),
+                &format!("{error:#}"),
+            );
+            last_progress = Instant::now();
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
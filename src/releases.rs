@@ -0,0 +1,239 @@
+use std::{collections::HashMap, process};
+
+use crate::prelude::*;
+
+/// Label storing the release id on built images
+pub const RELEASE_LABEL: &str = "dploy.release";
+
+/// Label storing the hash of the docker context the image was built from
+pub const CONTEXT_HASH_LABEL: &str = "dploy.context_hash";
+
+/// Tag which always points to the release the app container runs
+pub const CURRENT_TAG: &str = "latest";
+
+const CONFLICT_STATUS_CODE: u16 = 409;
+
+#[derive(Debug, Clone)]
+pub struct Release {
+    id: String,
+    image_id: String,
+    created: i64,
+    size: i64,
+}
+
+impl Release {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn image_id(&self) -> &str {
+        &self.image_id
+    }
+
+    /// Unix timestamp of the image creation
+    pub fn created(&self) -> i64 {
+        self.created
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+}
+
+/// Release id is a UTC timestamp, followed by the short git sha when available.
+/// Ids sort in the order of creation
+pub fn new_release_id() -> String {
+    let timestamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+
+    match git_short_sha() {
+        Some(sha) => format!("{timestamp}-{sha}"),
+        None => timestamp.to_string(),
+    }
+}
+
+fn git_short_sha() -> Option<String> {
+    let output = process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .stderr(process::Stdio::null())
+        .output()
+        .ok()?;
+
+    let sha = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+
+    (output.status.success() && !sha.is_empty()).then_some(sha)
+}
+
+/// Releases of the image, newest first
+pub async fn list(docker: &bollard::Docker, image_name: &str) -> Result<Vec<Release>> {
+    let images = docker
+        .list_images(Some(bollard::query_parameters::ListImagesOptions {
+            filters: Some(HashMap::from([(
+                "reference".to_owned(),
+                vec![image_name.to_owned()],
+            )])),
+            ..Default::default()
+        }))
+        .await?;
+
+    let prefix = format!("{image_name}:");
+
+    let mut releases = images
+        .into_iter()
+        .flat_map(|image| {
+            image
+                .repo_tags
+                .iter()
+                .filter_map(|repo_tag| repo_tag.strip_prefix(&prefix))
+                .filter(|tag| *tag != CURRENT_TAG)
+                .map(|tag| Release {
+                    id: tag.to_owned(),
+                    image_id: image.id.clone(),
+                    created: image.created,
+                    size: image.size,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    releases.sort_by(|a, b| b.id.cmp(&a.id));
+
+    Ok(releases)
+}
+
+/// Image id the current tag points to
+pub async fn current_image_id(
+    docker: &bollard::Docker,
+    image_name: &str,
+) -> Result<Option<String>> {
+    match docker
+        .inspect_image(&format!("{image_name}:{CURRENT_TAG}"))
+        .await
+    {
+        Ok(image) => Ok(image.id),
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Points the current tag to any image reference or id
+pub async fn tag_current(docker: &bollard::Docker, image_name: &str, source: &str) -> Result<()> {
+    docker
+        .tag_image(
+            source,
+            Some(bollard::query_parameters::TagImageOptions {
+                repo: Some(image_name.to_owned()),
+                tag: Some(CURRENT_TAG.to_owned()),
+            }),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Removes all releases except the newest `keep` ones and the current one
+pub async fn prune(docker: &bollard::Docker, image_name: &str, keep: usize) -> Result<Vec<String>> {
+    let current_image_id = current_image_id(docker, image_name).await?;
+    let mut removed = vec![];
+
+    for release in list(docker, image_name).await?.into_iter().skip(keep) {
+        if current_image_id.as_deref() == Some(release.image_id()) {
+            continue;
+        }
+
+        let result = docker
+            .remove_image(
+                &format!("{image_name}:{}", release.id()),
+                None::<bollard::query_parameters::RemoveImageOptions>,
+                None,
+            )
+            .await;
+
+        match result {
+            Ok(_) => removed.push(release.id),
+            // the image is still used by some container
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: CONFLICT_STATUS_CODE,
+                ..
+            }) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Removes all releases and the current tag of the image.
+/// Returns the removed references
+pub async fn remove_all(docker: &bollard::Docker, image_name: &str) -> Result<Vec<String>> {
+    let references = list(docker, image_name)
+        .await?
+        .iter()
+        .map(|release| format!("{image_name}:{}", release.id()))
+        .chain(std::iter::once(format!("{image_name}:{CURRENT_TAG}")))
+        .collect::<Vec<_>>();
+
+    let mut removed = vec![];
+
+    for reference in references {
+        match docker
+            .remove_image(
+                &reference,
+                None::<bollard::query_parameters::RemoveImageOptions>,
+                None,
+            )
+            .await
+        {
+            Ok(_) => removed.push(reference),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Untagged images left behind when a release is built again under the same tag.
+/// Returns the ids of the removed images, or of the ones which would be removed
+pub async fn remove_dangling(docker: &bollard::Docker, dry_run: bool) -> Result<Vec<String>> {
+    let images = docker
+        .list_images(Some(bollard::query_parameters::ListImagesOptions {
+            filters: Some(HashMap::from([
+                ("dangling".to_owned(), vec!["true".to_owned()]),
+                ("label".to_owned(), vec![RELEASE_LABEL.to_owned()]),
+            ])),
+            ..Default::default()
+        }))
+        .await?;
+
+    let mut removed = vec![];
+
+    for image in images {
+        if dry_run {
+            removed.push(image.id);
+            continue;
+        }
+
+        match docker
+            .remove_image(
+                &image.id,
+                None::<bollard::query_parameters::RemoveImageOptions>,
+                None,
+            )
+            .await
+        {
+            Ok(_) => removed.push(image.id),
+            // the image is still used by some container
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: CONFLICT_STATUS_CODE | 404,
+                ..
+            }) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(removed)
+}
@@ -0,0 +1,87 @@
+use rand::{distributions::Alphanumeric, Rng};
+
+use crate::{context, helper, prelude::*, services::ServiceKind};
+
+const SECRETS_DIR_NAME: &str = ".secrets";
+
+const GENERATED_SECRET_LENGTH: usize = 32;
+
+/// Reads the secret from the file or writes one of the candidates to it.
+/// The legacy value is used when the service data was already initialised
+/// before dploy started generating secrets
+const LOAD_OR_GENERATE_SCRIPT: &str = r#"
+set -e
+dir="$DPLOY_VOLUMES_DIR/$DPLOY_SECRETS_DIR"
+file="$dir/$DPLOY_SECRET_NAME"
+if [ ! -s "$file" ]; then
+    mkdir -p "$dir"
+    chmod 700 "$dir"
+    if [ -n "$DPLOY_LEGACY_VOLUME" ] && [ -n "$(ls -A "$DPLOY_VOLUMES_DIR/$DPLOY_LEGACY_VOLUME" 2>/dev/null)" ]; then
+        printf '%s' "$DPLOY_LEGACY_VALUE" > "$file"
+    else
+        printf '%s' "$DPLOY_SECRET_CANDIDATE" > "$file"
+    fi
+    chmod 600 "$file"
+fi
+cat "$file"
+"#;
+
+/// Value which was used by the service before the secret was persisted
+pub struct LegacySecret<'a> {
+    /// Inner path of the volume which is non-empty if the service was initialised
+    pub volume: &'a str,
+    pub value: &'a str,
+}
+
+/// Returns the secret stored next to the service volumes on the target host,
+/// generating and persisting a new one on the first call
+pub async fn load_or_generate(
+    docker: &bollard::Docker,
+    context: &context::Context,
+    service_kind: ServiceKind,
+    secret_name: &str,
+    legacy: Option<LegacySecret<'_>>,
+) -> Result<String> {
+    let (legacy_volume, legacy_value) = legacy
+        .map(|legacy| {
+            (
+                context::Context::volume_name_of(legacy.volume),
+                legacy.value.to_owned(),
+            )
+        })
+        .unwrap_or_default();
+
+    let candidate = generate_secret();
+
+    let output = helper::run_volumes_script(
+        docker,
+        context,
+        service_kind,
+        LOAD_OR_GENERATE_SCRIPT,
+        &[
+            ("DPLOY_SECRETS_DIR", SECRETS_DIR_NAME),
+            ("DPLOY_SECRET_NAME", secret_name),
+            ("DPLOY_SECRET_CANDIDATE", &candidate),
+            ("DPLOY_LEGACY_VOLUME", &legacy_volume),
+            ("DPLOY_LEGACY_VALUE", &legacy_value),
+        ],
+    )
+    .await?;
+
+    if output.exit_code != 0 || output.stdout.is_empty() {
+        bail!(
+            "Could not load {secret_name} of {service_kind}: {}",
+            output.stderr.trim()
+        );
+    }
+
+    Ok(output.stdout)
+}
+
+fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(GENERATED_SECRET_LENGTH)
+        .map(char::from)
+        .collect()
+}
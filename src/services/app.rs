@@ -1,11 +1,14 @@
-use std::{collections::HashMap, env, path};
+use std::{collections::HashMap, env, path, time::Duration};
 
-use anyhow::Result;
-use bollard::{container, image, models};
+use anyhow::{Context as _, Result};
+use bollard::{models, query_parameters};
 
 use crate::{
+    config, constants,
     context::Context,
+    health::{HealthProbe, Healthcheck},
     network::DPLOY_NETWORK,
+    releases::{CURRENT_TAG, RELEASE_LABEL},
     utils::{network::free_port, string::escape_sh},
 };
 
@@ -17,75 +20,317 @@ const SERVICE_KIND: ServiceKind = ServiceKind::App;
 pub struct AppService {
     app_name: String,
     image_name: String,
+
+    /// Prebuilt image pulled instead of building one
+    prebuilt_image: Option<String>,
+
     container_name: String,
+    command: Option<Vec<String>>,
+    entrypoint: Option<Vec<String>>,
+    user: Option<String>,
+    workdir: Option<String>,
     env_vars: Vec<(String, String)>,
     ports_mapping: Vec<(u16, u16)>,
     volumes: Vec<String>,
     dockerfile: String,
     docker_context: String,
+    build: config::BuildConfig,
     relative_path: path::PathBuf,
+    healthcheck: Option<Healthcheck>,
+    default_probe: Option<Healthcheck>,
+    proxy: Vec<config::ProxyConfig>,
+}
+
+/// Config of a single app, resolved from the top level or from an `[[apps]]` entry
+struct AppServiceConfig<'a> {
+    name: &'a str,
+    dockerfile: &'a str,
+    docker_context: &'a str,
+    image: Option<&'a str>,
+    command: Option<&'a [String]>,
+    entrypoint: Option<&'a [String]>,
+    user: Option<&'a str>,
+    workdir: Option<&'a str>,
+    env: &'a [String],
+    volumes: &'a [String],
+    ports: &'a [u16],
+    healthcheck: Option<&'a config::HealthcheckConfig>,
+    proxy: &'a [config::ProxyConfig],
 }
 
 impl AppService {
-    pub fn from_context(context: &Context, env_vars: Vec<(String, String)>) -> Self {
-        let ports_mapping = context
-            .should_expose_app_service_to_host()
-            .then(|| {
-                context
-                    .app_config()
-                    .ports(context.override_context())
-                    .iter()
-                    .map(|port| (free_port(), *port))
-                    .collect()
-            })
-            .unwrap_or_else(std::vec::Vec::new);
+    /// The top-level app
+    pub fn from_context(context: &Context, env_vars: Vec<(String, String)>) -> Result<Self> {
+        let app_config = context.app_config();
+        let override_context = context.override_context();
+
+        Self::new(
+            context,
+            AppServiceConfig {
+                name: app_config.name(override_context),
+                dockerfile: app_config.dockerfile(override_context),
+                docker_context: app_config.context(override_context),
+                image: app_config.image(override_context),
+                command: app_config.command(override_context),
+                entrypoint: app_config.entrypoint(override_context),
+                user: app_config.user(override_context),
+                workdir: app_config.workdir(override_context),
+                env: app_config.env(override_context),
+                volumes: app_config.volumes(override_context),
+                ports: app_config.ports(override_context),
+                healthcheck: app_config.healthcheck(override_context),
+                proxy: app_config.proxy(override_context),
+            },
+            env_vars,
+        )
+    }
+
+    /// App of an `[[apps]]` entry. Without its own image source
+    /// it's built the same way as the top-level app
+    pub fn from_extra_config(
+        context: &Context,
+        extra_config: &config::ExtraAppConfig,
+        env_vars: Vec<(String, String)>,
+    ) -> Result<Self> {
+        let app_config = context.app_config();
+        let override_context = context.override_context();
+
+        let has_own_source = extra_config.dockerfile().is_some()
+            || extra_config.context().is_some()
+            || extra_config.image().is_some();
+
+        let image = if has_own_source {
+            extra_config.image()
+        } else {
+            app_config.image(override_context)
+        };
+
+        Self::new(
+            context,
+            AppServiceConfig {
+                name: extra_config.name(),
+                dockerfile: extra_config
+                    .dockerfile()
+                    .unwrap_or(app_config.dockerfile(override_context)),
+                docker_context: extra_config
+                    .context()
+                    .unwrap_or(app_config.context(override_context)),
+                image,
+                command: extra_config.command(),
+                entrypoint: extra_config.entrypoint(),
+                user: extra_config.user(),
+                workdir: extra_config.workdir(),
+                env: extra_config.env(),
+                volumes: extra_config.volumes(),
+                ports: extra_config.ports(),
+                healthcheck: extra_config.healthcheck(),
+                proxy: extra_config.proxy(),
+            },
+            env_vars,
+        )
+    }
+
+    fn new(
+        context: &Context,
+        config: AppServiceConfig,
+        env_vars: Vec<(String, String)>,
+    ) -> Result<Self> {
+        let ports_mapping = if context.should_expose_app_service_to_host() {
+            config
+                .ports
+                .iter()
+                .map(|port| (free_port(), *port))
+                .collect()
+        } else {
+            vec![]
+        };
 
         let mut env_vars = env_vars;
 
         // TODO: refactor this to store all env in context
         // this will allow to also parameterize other services
-        for env_name in context.app_config().env(context.override_context()) {
+        for env_name in config.env {
             env_vars.push((env_name.to_owned(), env::var(env_name).unwrap_or_default()));
         }
 
-        Self {
-            app_name: context
-                .app_config()
-                .name(context.override_context())
-                .to_owned(),
-            image_name: context.container_name_of(SERVICE_KIND),
-            container_name: context.container_name_of(SERVICE_KIND),
+        let healthcheck = config
+            .healthcheck
+            .map(Healthcheck::from_config)
+            .transpose()?;
+
+        // without an explicit check the proxied port must at least accept connections
+        // before the proxy is switched to the new container
+        let default_probe = context
+            .should_create_proxy_service()
+            .then(|| config.proxy.first())
+            .flatten()
+            .map(|proxy| {
+                Healthcheck::new(
+                    HealthProbe::Tcp { port: proxy.port },
+                    Duration::from_secs(constants::DEFAULT_HEALTHCHECK_TIMEOUT),
+                    Duration::from_secs(constants::DEFAULT_HEALTHCHECK_INTERVAL),
+                )
+            });
+
+        let container_name = context.app_container_name_of(config.name);
+
+        // prebuilt images are never built, so their context doesn't have to exist
+        let relative_path = match config.image {
+            Some(_) => path::PathBuf::new(),
+            None => context.config_dir_relative_to_docker_context(config.docker_context)?,
+        };
+
+        Ok(Self {
+            app_name: config.name.to_owned(),
+            image_name: container_name.clone(),
+            prebuilt_image: config.image.map(str::to_owned),
+            container_name,
+            command: config.command.map(<[String]>::to_vec),
+            entrypoint: config.entrypoint.map(<[String]>::to_vec),
+            user: config.user.map(str::to_owned),
+            workdir: config.workdir.map(str::to_owned),
             env_vars,
             ports_mapping,
-            volumes: context
-                .app_config()
-                .volumes(context.override_context())
-                .iter()
-                .map(|volume| volume.to_owned())
-                .collect(),
-            dockerfile: context
-                .app_config()
-                .dockerfile(context.override_context())
-                .to_owned(),
-            docker_context: context
-                .app_config()
-                .context(context.override_context())
-                .to_owned(),
-            relative_path: context.config_dir_relative_to_docker_context(),
-        }
+            volumes: config.volumes.to_vec(),
+            dockerfile: config.dockerfile.to_owned(),
+            build: context.app_config().build(context.override_context()),
+            docker_context: config.docker_context.to_owned(),
+            relative_path,
+            healthcheck,
+            default_probe,
+            proxy: config.proxy.to_vec(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.app_name
+    }
+
+    pub fn container_name(&self) -> &str {
+        &self.container_name
+    }
+
+    pub fn dockerfile(&self) -> &str {
+        &self.dockerfile
+    }
+
+    pub fn docker_context(&self) -> &str {
+        &self.docker_context
+    }
+
+    /// Path of the config directory relative to the working directory,
+    /// going through the docker context
+    pub fn relative_path(&self) -> &path::Path {
+        &self.relative_path
+    }
+
+    /// Domains routed to the app
+    pub fn proxy(&self) -> &[config::ProxyConfig] {
+        &self.proxy
     }
 
     pub fn ports_mapping(&self) -> &[(u16, u16)] {
         &self.ports_mapping
     }
 
-    pub fn to_image_build_config(&self) -> Result<image::BuildImageOptions<String>> {
+    /// Health check configured by the user, runs inside the container
+    pub fn healthcheck(&self) -> Option<&Healthcheck> {
+        self.healthcheck.as_ref()
+    }
+
+    /// Probe run from the network when there is no configured health check
+    pub fn default_probe(&self) -> Option<&Healthcheck> {
+        self.default_probe.as_ref()
+    }
+
+    pub fn image_name(&self) -> &str {
+        &self.image_name
+    }
+
+    pub fn prebuilt_image(&self) -> Option<&str> {
+        self.prebuilt_image.as_deref()
+    }
+
+    pub fn build(&self) -> &config::BuildConfig {
+        &self.build
+    }
+
+    /// Build args with the values of env-based ones resolved.
+    /// These may hold secrets, so they must never be printed
+    pub fn build_args(&self) -> Result<HashMap<String, String>> {
+        self.build
+            .args()
+            .iter()
+            .map(|(name, arg)| {
+                let value = match arg {
+                    config::BuildArgConfig::Value(value) => value.clone(),
+                    config::BuildArgConfig::Env { env } => env::var(env).with_context(|| {
+                        format!("Build arg {name} is taken from {env}, which is not set")
+                    })?,
+                };
+
+                Ok((name.clone(), value))
+            })
+            .collect()
+    }
+
+    pub fn to_image_build_config(
+        &self,
+        release: &str,
+    ) -> Result<query_parameters::BuildImageOptions> {
         let dockerfile = self.relative_path.join(&self.dockerfile);
         let dockerfile = dockerfile.strip_prefix(&self.docker_context)?;
 
-        Ok(image::BuildImageOptions {
-            t: self.image_name.clone(),
+        let mut labels = self
+            .build
+            .labels()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<HashMap<_, _>>();
+        labels.insert(RELEASE_LABEL.to_owned(), release.to_owned());
+
+        Ok(query_parameters::BuildImageOptions {
+            t: Some(format!("{}:{release}", self.image_name)),
             dockerfile: dockerfile.to_string_lossy().to_string(),
+            buildargs: Some(self.build_args()?),
+            platform: self.build.platform().unwrap_or_default().to_owned(),
+            target: self.build.target().unwrap_or_default().to_owned(),
+            cachefrom: Some(self.build.cache_from().to_vec()),
+            nocache: self.build.no_cache(),
+            labels: Some(labels),
+            ..Default::default()
+        })
+    }
+
+    /// Config of a one-off container running the command with the app image, env,
+    /// user and working directory.
+    /// The entrypoint of the image is replaced, so the command runs in a shell
+    pub fn to_hook_container_config(
+        &self,
+        context: &Context,
+        image_tag: &str,
+        command: &str,
+    ) -> Result<models::ContainerCreateBody> {
+        let container_config = self.to_container_config(context)?;
+        let config = container_config.config();
+
+        Ok(models::ContainerCreateBody {
+            image: Some(format!("{}:{image_tag}", self.image_name)),
+            entrypoint: Some(vec!["sh".to_owned(), "-c".to_owned()]),
+            cmd: Some(vec![command.to_owned()]),
+            env: config.env.clone(),
+            user: config.user.clone(),
+            working_dir: config.working_dir.clone(),
+            networking_config: config.networking_config.clone(),
+
+            host_config: Some(models::HostConfig {
+                mounts: config
+                    .host_config
+                    .as_ref()
+                    .and_then(|host_config| host_config.mounts.clone()),
+                ..Default::default()
+            }),
+
             ..Default::default()
         })
     }
@@ -93,40 +338,47 @@ impl AppService {
 
 impl ToContainerConfig for AppService {
     fn to_container_config(&self, context: &Context) -> Result<ContainerConfig> {
-        let mut host_config = models::HostConfig::default();
+        let host_config = models::HostConfig {
+            mounts: Some(
+                self.volumes
+                    .iter()
+                    .map(|volume| context.container_mount(&self.container_name, volume))
+                    .collect(),
+            ),
 
-        host_config.mounts = Some(
-            self.volumes
-                .iter()
-                .map(|volume| context.mount(SERVICE_KIND, volume))
-                .collect(),
-        );
+            port_bindings: Some(
+                self.ports_mapping
+                    .iter()
+                    .map(|(host_port, container_port)| {
+                        (
+                            // TODO: DPLY-18 support not only tcp
+                            format!("{}/tcp", container_port),
+                            Some(vec![models::PortBinding {
+                                host_ip: Some("127.0.0.1".to_owned()),
+                                host_port: Some(format!("{}", host_port)),
+                            }]),
+                        )
+                    })
+                    .collect(),
+            ),
+
+            restart_policy: Some(models::RestartPolicy {
+                name: Some(models::RestartPolicyNameEnum::ALWAYS),
+                ..Default::default()
+            }),
 
-        host_config.port_bindings = Some(
-            self.ports_mapping
-                .iter()
-                .map(|(host_port, container_port)| {
-                    (
-                        // TODO: DPLY-18 support not only tcp
-                        format!("{}/tcp", container_port),
-                        Some(vec![models::PortBinding {
-                            host_ip: Some("127.0.0.1".to_owned()),
-                            host_port: Some(format!("{}", host_port)),
-                        }]),
-                    )
-                })
-                .collect(),
-        );
-
-        host_config.restart_policy = Some(models::RestartPolicy {
-            name: Some(models::RestartPolicyNameEnum::ALWAYS),
             ..Default::default()
-        });
+        };
 
-        let config = container::Config {
+        let config = models::ContainerCreateBody {
             image: Some(self.image_name.clone()),
             hostname: Some(self.container_name.clone()),
             domainname: Some(self.container_name.clone()),
+            labels: Some(context.labels_of(SERVICE_KIND)),
+            cmd: self.command.clone(),
+            entrypoint: self.entrypoint.clone(),
+            user: self.user.clone(),
+            working_dir: self.workdir.clone(),
 
             env: Some(
                 self.env_vars
@@ -149,11 +401,13 @@ impl ToContainerConfig for AppService {
 
             host_config: Some(host_config),
 
-            networking_config: Some(container::NetworkingConfig {
-                endpoints_config: HashMap::from([(
+            healthcheck: self.healthcheck.as_ref().map(Healthcheck::to_health_config),
+
+            networking_config: Some(models::NetworkingConfig {
+                endpoints_config: Some(HashMap::from([(
                     DPLOY_NETWORK.to_owned(),
                     models::EndpointSettings::default(),
-                )]),
+                )])),
             }),
 
             ..Default::default()
@@ -162,6 +416,7 @@ impl ToContainerConfig for AppService {
         Ok(ContainerConfig::new(
             self.container_name.clone(),
             self.image_name.clone(),
+            CURRENT_TAG.to_owned(),
             config,
         ))
     }
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{bail, Result};
+use bollard::models;
+
+use crate::{
+    constants,
+    context::{Context, HostPortBinding},
+    health::{HealthProbe, Healthcheck},
+    network::DPLOY_NETWORK,
+    readiness::{Readiness, ReadinessCheck},
+};
+
+use super::{
+    ConnectionInfo, ContainerConfig, EnvVars, ServiceKind, ToContainerConfig, ToReadiness,
+};
+
+const DEFAULT_PORT: u16 = 6379;
+
+const DEFAULT_IMAGE_NAME: &str = "eqalpha/keydb";
+/// Keydb publishes versioned tags only per architecture,
+/// so the default tag is picked for the architecture of the daemon
+const DEFAULT_IMAGE_VERSION: &str = "v6.3.4";
+/// Custom images without a version keep the tag docker would pick
+const CUSTOM_IMAGE_TAG: &str = "latest";
+const DATA_PATH: &str = "/data";
+
+const READY_COMMAND: &str = "keydb-cli ping | grep -q PONG";
+
+const SERVICE_KIND: ServiceKind = ServiceKind::Keydb;
+
+pub struct KeydbService {
+    expose_url_to_env: Option<String>,
+
+    image_name: String,
+    image_tag: String,
+
+    binding: HostPortBinding,
+}
+
+impl KeydbService {
+    pub async fn from_context(context: &Context, docker: &bollard::Docker) -> Result<Option<Self>> {
+        let Some(config) = context.app_config().keydb(context.override_context()) else {
+            return Ok(None);
+        };
+
+        let image_name = config.image().image().unwrap_or(DEFAULT_IMAGE_NAME);
+        let image_tag = match config.image().version() {
+            Some(version) => version.to_owned(),
+            None if image_name == DEFAULT_IMAGE_NAME => default_image_tag(docker).await?,
+            None => CUSTOM_IMAGE_TAG.to_owned(),
+        };
+
+        let binding = context
+            .host_port_binding_of(docker, SERVICE_KIND, DEFAULT_PORT)
+            .await?;
+
+        Ok(Some(Self {
+            expose_url_to_env: config.expose_url_to_env().map(ToOwned::to_owned),
+
+            image_name: image_name.to_owned(),
+            image_tag,
+
+            binding,
+        }))
+    }
+
+    pub fn construct_url(&self, host: &str, port: u16) -> String {
+        format!("redis://{}:{}", host, port)
+    }
+
+    pub fn inner_url(&self) -> String {
+        let inner_port = self.binding.inner_port();
+        let inner_host = self.binding.inner_host();
+
+        self.construct_url(inner_host, inner_port)
+    }
+
+    pub fn host_url(&self) -> Option<String> {
+        let host_port = self.binding.host_port();
+        let host_host = self.binding.host_host();
+
+        host_port.map(|port| self.construct_url(host_host, port))
+    }
+}
+
+impl EnvVars for KeydbService {
+    fn env_vars(&self) -> Vec<(String, String)> {
+        let mut vars = Vec::new();
+
+        if let Some(expose_url_to_env) = &self.expose_url_to_env {
+            vars.push((expose_url_to_env.clone(), self.inner_url()))
+        }
+
+        vars
+    }
+}
+
+impl ConnectionInfo for KeydbService {
+    fn connection_info(&self) -> Vec<String> {
+        vec![self.host_url()].into_iter().flatten().collect()
+    }
+}
+
+impl ToReadiness for KeydbService {
+    fn to_readiness(&self, context: &Context) -> Readiness {
+        let container_name = context.container_name_of(SERVICE_KIND);
+
+        let checks = std::iter::once(ReadinessCheck::Exec {
+            container_name: container_name.clone(),
+            command: READY_COMMAND.to_owned(),
+        })
+        .chain(ReadinessCheck::host_tcp_of(&self.binding))
+        .collect();
+
+        Readiness::new(container_name, checks)
+    }
+}
+
+impl ToContainerConfig for KeydbService {
+    fn to_container_config(&self, context: &Context) -> Result<ContainerConfig> {
+        let name = context.container_name_of(SERVICE_KIND);
+
+        let host_config = models::HostConfig {
+            mounts: Some(vec![context.mount(SERVICE_KIND, DATA_PATH)]),
+            port_bindings: Some(self.binding.to_port_binding()),
+
+            restart_policy: Some(models::RestartPolicy {
+                name: Some(models::RestartPolicyNameEnum::ALWAYS),
+                ..Default::default()
+            }),
+
+            ..Default::default()
+        };
+
+        let config = models::ContainerCreateBody {
+            image: Some(format!("{}:{}", self.image_name, self.image_tag)),
+            hostname: Some(name.clone()),
+            domainname: Some(name.clone()),
+            labels: Some(context.labels_of(SERVICE_KIND)),
+
+            cmd: Some(
+                [
+                    "keydb-server",
+                    "/etc/keydb/keydb.conf",
+                    "--dir",
+                    DATA_PATH,
+                    "--appendonly",
+                    "yes",
+                    "--bind",
+                    "0.0.0.0",
+                    "--protected-mode",
+                    "no",
+                ]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            ),
+
+            networking_config: Some(models::NetworkingConfig {
+                endpoints_config: Some(HashMap::from([(
+                    DPLOY_NETWORK.to_owned(),
+                    models::EndpointSettings::default(),
+                )])),
+            }),
+
+            host_config: Some(host_config),
+
+            healthcheck: Some(
+                Healthcheck::new(
+                    HealthProbe::Command(READY_COMMAND.to_owned()),
+                    Duration::from_secs(constants::DEFAULT_HEALTHCHECK_TIMEOUT),
+                    Duration::from_secs(constants::DEFAULT_HEALTHCHECK_INTERVAL),
+                )
+                .to_health_config(),
+            ),
+
+            ..Default::default()
+        };
+
+        Ok(ContainerConfig::new(
+            name,
+            self.image_name.clone(),
+            self.image_tag.clone(),
+            config,
+        ))
+    }
+}
+
+async fn default_image_tag(docker: &bollard::Docker) -> Result<String> {
+    let arch = docker.version().await?.arch.unwrap_or_default();
+
+    match arch.as_str() {
+        "amd64" => Ok(format!("x86_64_{DEFAULT_IMAGE_VERSION}")),
+        "arm64" => Ok(format!("arm64_{DEFAULT_IMAGE_VERSION}")),
+        _ => bail!(
+            "Keydb {DEFAULT_IMAGE_VERSION} has no image for the {arch} architecture, please set version in [keydb]"
+        ),
+    }
+}
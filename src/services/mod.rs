@@ -1,11 +1,13 @@
-use std::fmt;
+use std::{collections::HashSet, fmt};
 
-use anyhow::Result;
-use bollard::container;
+use anyhow::{bail, Result};
+use bollard::models;
+use sha2::{Digest, Sha256};
 
-use crate::context::Context;
+use crate::{context::Context, readiness::Readiness};
 
 pub mod app;
+pub mod keydb;
 pub mod postgres;
 pub mod proxy;
 
@@ -49,25 +51,58 @@ pub trait EnvVars {
     fn env_vars(&self) -> Vec<(String, String)>;
 }
 
+/// Label storing the hash of the image and config the container was created from
+pub const FINGERPRINT_LABEL: &str = "dploy.fingerprint";
+
+/// Label storing the network alias the proxy should send traffic to
+pub const UPSTREAM_LABEL: &str = "dploy.upstream";
+
+/// Names which can't be used for apps, see `validate_app_names`
+const RESERVED_APP_NAMES: &[&str] = &["app", "postgres", "keydb", "proxy"];
+
+/// Labels identifying the containers created by dploy
+pub const PROJECT_LABEL: &str = "dploy.project";
+pub const NAMESPACE_LABEL: &str = "dploy.namespace";
+pub const KIND_LABEL: &str = "dploy.kind";
+pub const VERSION_LABEL: &str = "dploy.version";
+
+/// File written into the container before it's started
+#[derive(Debug, Clone)]
+pub struct ContainerFile {
+    pub dir: String,
+    pub name: String,
+    pub contents: String,
+}
+
 pub struct ContainerConfig {
     container_name: String,
     image_name: String,
-    config: container::Config<String>,
+    image_tag: String,
+    config: models::ContainerCreateBody,
+    files: Vec<ContainerFile>,
 }
 
 impl ContainerConfig {
     pub fn new(
         container_name: String,
         image_name: String,
-        config: container::Config<String>,
+        image_tag: String,
+        config: models::ContainerCreateBody,
     ) -> Self {
         Self {
             container_name,
             image_name,
+            image_tag,
             config,
+            files: vec![],
         }
     }
 
+    pub fn with_files(mut self, files: Vec<ContainerFile>) -> Self {
+        self.files = files;
+        self
+    }
+
     pub fn container_name(&self) -> &str {
         &self.container_name
     }
@@ -76,50 +111,140 @@ impl ContainerConfig {
         &self.image_name
     }
 
-    pub fn config(&self) -> &container::Config<String> {
+    pub fn image_tag(&self) -> &str {
+        &self.image_tag
+    }
+
+    /// Full image reference including the tag
+    pub fn image(&self) -> String {
+        format!("{}:{}", self.image_name, self.image_tag)
+    }
+
+    pub fn config(&self) -> &models::ContainerCreateBody {
         &self.config
     }
+
+    pub fn files(&self) -> &[ContainerFile] {
+        &self.files
+    }
+
+    /// Hash of the image id, the container config and its files, used to detect drift.
+    /// Config is converted to a json value first, so maps are hashed in a stable order.
+    /// The dploy version is left out, so upgrading dploy doesn't recreate the containers
+    pub fn fingerprint(&self, image_id: &str) -> Result<String> {
+        let mut config = self.config.clone();
+
+        if let Some(labels) = config.labels.as_mut() {
+            labels.remove(VERSION_LABEL);
+        }
+
+        let config = serde_json::to_value(&config)?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(image_id.as_bytes());
+        hasher.update(config.to_string().as_bytes());
+
+        for file in &self.files {
+            hasher.update(file.dir.as_bytes());
+            hasher.update(file.name.as_bytes());
+            hasher.update(file.contents.as_bytes());
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Container config with the fingerprint stored as a label
+    pub fn config_with_fingerprint(&self, fingerprint: &str) -> models::ContainerCreateBody {
+        let mut config = self.config.clone();
+
+        config
+            .labels
+            .get_or_insert_with(Default::default)
+            .insert(FINGERPRINT_LABEL.to_owned(), fingerprint.to_owned());
+
+        config
+    }
 }
 
 pub trait ToContainerConfig {
     fn to_container_config(&self, context: &Context) -> Result<ContainerConfig>;
 }
 
+/// Checks which must pass before the service is considered ready to accept connections
+pub trait ToReadiness {
+    fn to_readiness(&self, context: &Context) -> Readiness;
+}
+
 pub trait ConnectionInfo {
     fn connection_info(&self) -> Vec<String>;
 }
 
 pub struct Services {
-    app: Option<app::AppService>,
+    /// The top-level app first, followed by the `[[apps]]` entries
+    apps: Vec<app::AppService>,
     postgres: Option<postgres::PostgresService>,
+    keydb: Option<keydb::KeydbService>,
     proxy: proxy::ProxyService,
 }
 
 impl Services {
-    pub fn from_context(context: &Context) -> Self {
+    pub async fn from_context(context: &Context, docker: &bollard::Docker) -> Result<Self> {
         let mut app_service_env_vars = vec![];
 
-        let postgres = postgres::PostgresService::from_context(context);
+        let postgres = postgres::PostgresService::from_context(context, docker).await?;
 
         if let Some(postgres) = &postgres {
             app_service_env_vars.extend(postgres.env_vars());
         }
 
-        let app = context
-            .should_create_app_service()
-            .then(|| app::AppService::from_context(context, app_service_env_vars));
+        validate_proxy_image(context)?;
 
-        let proxy = proxy::ProxyService::from_context(context);
+        let keydb = keydb::KeydbService::from_context(context, docker).await?;
 
-        Self {
-            app,
+        if let Some(keydb) = &keydb {
+            app_service_env_vars.extend(keydb.env_vars());
+        }
+
+        let mut apps = vec![];
+
+        if context.should_create_app_service() {
+            validate_app_names(context)?;
+
+            apps.push(app::AppService::from_context(
+                context,
+                app_service_env_vars.clone(),
+            )?);
+
+            for extra_config in context.app_config().apps(context.override_context()) {
+                apps.push(app::AppService::from_extra_config(
+                    context,
+                    extra_config,
+                    app_service_env_vars.clone(),
+                )?);
+            }
+        }
+
+        let proxy = proxy::ProxyService::from_context(context, docker).await?;
+
+        Ok(Self {
+            apps,
             postgres,
+            keydb,
             proxy,
-        }
+        })
     }
 
+    /// The top-level app
     pub fn app(&self) -> Option<&app::AppService> {
-        self.app.as_ref()
+        self.apps.first()
+    }
+
+    pub fn apps(&self) -> &[app::AppService] {
+        &self.apps
+    }
+
+    pub fn proxy(&self) -> &proxy::ProxyService {
+        &self.proxy
     }
 
     pub fn to_container_configs(&self, context: &Context) -> Result<Vec<ContainerConfig>> {
@@ -129,6 +254,10 @@ impl Services {
             configs.push(postgres.to_container_config(context)?);
         }
 
+        if let Some(keydb) = &self.keydb {
+            configs.push(keydb.to_container_config(context)?);
+        }
+
         if context.should_create_proxy_service() {
             configs.push(self.proxy.to_container_config(context)?);
         }
@@ -143,9 +272,41 @@ impl Services {
             configs.push(postgres.to_container_config(context)?);
         }
 
+        if let Some(keydb) = &self.keydb {
+            configs.push(keydb.to_container_config(context)?);
+        }
+
+        // the singleton proxy is shared between projects, so only the local one is stopped
+        if context.is_local_proxy() && context.app_config().has_proxy(context.override_context()) {
+            configs.push(self.proxy.to_container_config(context)?);
+        }
+
         Ok(configs)
     }
 
+    pub fn to_readiness(&self, context: &Context) -> Vec<Readiness> {
+        let mut readiness = vec![];
+
+        if let Some(postgres) = &self.postgres {
+            readiness.push(postgres.to_readiness(context));
+        }
+
+        if let Some(keydb) = &self.keydb {
+            readiness.push(keydb.to_readiness(context));
+        }
+
+        readiness
+    }
+
+    /// These actions run before any dependency is created
+    pub async fn pre_up(&self, context: &Context, docker: &bollard::Docker) -> Result<()> {
+        if let Some(postgres) = &self.postgres {
+            postgres.check_data_version(context, docker).await?;
+        }
+
+        Ok(())
+    }
+
     /// These actions run after all services have been created
     pub async fn post_up(&self, docker: &bollard::Docker) -> Result<()> {
         self.proxy.post_up(docker).await?;
@@ -167,6 +328,10 @@ impl Services {
             env_vars.extend(postgres.env_vars());
         }
 
+        if let Some(keydb) = &self.keydb {
+            env_vars.extend(keydb.env_vars());
+        }
+
         if let Some(expose_namespace_to_env) = context
             .app_config()
             .expose_namespace_to_env(context.override_context())
@@ -180,7 +345,7 @@ impl Services {
         env_vars
     }
 
-    pub fn connection_info(&self) -> Vec<(ServiceKind, String)> {
+    pub fn connection_info(&self, context: &Context) -> Vec<(ServiceKind, String)> {
         let mut infos = vec![];
 
         if let Some(postgres) = &self.postgres {
@@ -192,7 +357,16 @@ impl Services {
             );
         }
 
-        if let Some(app) = &self.app {
+        if let Some(keydb) = &self.keydb {
+            infos.extend(
+                keydb
+                    .connection_info()
+                    .into_iter()
+                    .map(|s| (ServiceKind::Keydb, s)),
+            );
+        }
+
+        for app in &self.apps {
             infos.extend(
                 app.connection_info()
                     .into_iter()
@@ -200,6 +374,146 @@ impl Services {
             );
         }
 
+        if context.should_create_proxy_service() {
+            infos.extend(
+                self.proxy
+                    .connection_info()
+                    .into_iter()
+                    .map(|s| (ServiceKind::Proxy, s)),
+            );
+        }
+
         infos
     }
 }
+
+/// Apps get containers named after them next to the dependencies,
+/// so their names must not clash with each other or with the services.
+/// `app` is reserved for selecting the top-level app with `-s`
+fn validate_app_names(context: &Context) -> Result<()> {
+    let mut names = HashSet::from([context.app_config().name(context.override_context())]);
+
+    for app in context.app_config().apps(context.override_context()) {
+        let name = app.name();
+
+        if RESERVED_APP_NAMES.contains(&name) {
+            bail!("App name {name} is reserved, please rename the app");
+        }
+
+        if !names.insert(name) {
+            bail!("App name {name} is used more than once, app names must be unique");
+        }
+    }
+
+    Ok(())
+}
+
+/// The proxy of deploys is shared by every project on the server,
+/// so a project can't choose its image
+fn validate_proxy_image(context: &Context) -> Result<()> {
+    let has_proxy_image = context
+        .app_config()
+        .proxy_image(context.override_context())
+        .is_some();
+
+    if has_proxy_image && context.should_create_proxy_service() && !context.is_local_proxy() {
+        bail!("proxy_image applies only to the local proxy of `dploy run --proxy`, please move it to an override for the `run` command");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{cli::Command, context::HostPortBinding};
+
+    use super::*;
+
+    fn container_config(labels: &[(&str, &str)]) -> ContainerConfig {
+        ContainerConfig::new(
+            "container".to_owned(),
+            "image".to_owned(),
+            "latest".to_owned(),
+            models::ContainerCreateBody {
+                labels: Some(
+                    labels
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect::<HashMap<_, _>>(),
+                ),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let labels = (0..32)
+            .map(|i| (format!("key{i}"), format!("value{i}")))
+            .collect::<Vec<_>>();
+        let labels = labels
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        let reversed_labels = labels.iter().rev().copied().collect::<Vec<_>>();
+
+        let config = container_config(&labels);
+        let same_config = container_config(&reversed_labels);
+        let other_config = container_config(&labels[1..]);
+
+        assert_eq!(
+            config.fingerprint("sha256:1").unwrap(),
+            same_config.fingerprint("sha256:1").unwrap()
+        );
+        assert_ne!(
+            config.fingerprint("sha256:1").unwrap(),
+            config.fingerprint("sha256:2").unwrap()
+        );
+        assert_ne!(
+            config.fingerprint("sha256:1").unwrap(),
+            other_config.fingerprint("sha256:1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_version() {
+        let config = container_config(&[(KIND_LABEL, "app"), (VERSION_LABEL, "0.0.1")]);
+        let upgraded_config = container_config(&[(KIND_LABEL, "app"), (VERSION_LABEL, "0.0.2")]);
+
+        assert_eq!(
+            config.fingerprint("sha256:1").unwrap(),
+            upgraded_config.fingerprint("sha256:1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_keeps_existing_host_port() {
+        let command = Command::Dev { command: None };
+        let dependency_config = |existing_host_port| {
+            let binding = HostPortBinding::new("postgres", 5432, &command, existing_host_port);
+
+            ContainerConfig::new(
+                "postgres".to_owned(),
+                "postgres".to_owned(),
+                "18".to_owned(),
+                models::ContainerCreateBody {
+                    host_config: Some(models::HostConfig {
+                        port_bindings: Some(binding.to_port_binding()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let config = dependency_config(Some(54321));
+        let next_config = dependency_config(Some(54321));
+
+        assert_eq!(
+            config.fingerprint("sha256:1").unwrap(),
+            next_config.fingerprint("sha256:1").unwrap()
+        );
+    }
+}
@@ -2,9 +2,12 @@ use std::{collections::HashMap, path::PathBuf};
 
 use itertools::Itertools;
 
-use crate::{context, docker, network, prelude::*, services};
+use crate::{
+    config, context, docker, network, prelude::*, presentation, services, utils::network::free_port,
+};
 
-const IMAGE_NAME: &str = "caddy";
+const DEFAULT_IMAGE_NAME: &str = "caddy";
+const DEFAULT_IMAGE_TAG: &str = "2";
 
 const CADDY_CONFIGS_INNER_DIR: &str = "/etc/caddy";
 const CADDY_CONFIG_INNER_FILE: &str = "Caddyfile";
@@ -15,52 +18,147 @@ const VOLUMES_MAPPINGS: &[(&str, &str)] = &[
     ("caddy/internal/config", "/config/"),
 ];
 
-const PORT_MAPPINGS: &[(u16, u16)] = &[(80, 80), (443, 443)];
+const HTTPS_PORT: u16 = 443;
+const PORT_MAPPINGS: &[(u16, u16)] = &[(80, 80), (HTTPS_PORT, HTTPS_PORT)];
 
 const GENERAL_CADDYFILE_CONTENTS: &str = "import /etc/caddy/*.caddy";
 
-const RELOAD_CADDY_COMMAND: &str = "caddy reload -c /etc/caddy/Caddyfile";
+const LOCAL_DOMAIN_SUFFIX: &str = ".localhost";
 
 const SERVICE_KIND: services::ServiceKind = services::ServiceKind::Proxy;
 
 pub struct ProxyService {
     name: String,
-    app_service_container_name: String,
+    image_name: String,
+    image_tag: String,
+    apps: Vec<ProxiedApp>,
     bindings: Vec<context::HostPortBinding>,
-    configs: Vec<ProxyServiceConfig>,
+    site: Site,
 }
 
-struct ProxyServiceConfig {
-    domain: String,
-    port: u16,
+/// App with its routes, stored in its own `.caddy` file
+struct ProxiedApp {
+    container_name: String,
+    configs: Vec<config::ProxyConfig>,
+}
+
+impl ProxiedApp {
+    fn config_file_name(&self) -> String {
+        format!("{}.caddy", self.container_name)
+    }
+
+    fn config_path(&self) -> String {
+        PathBuf::from(CADDY_CONFIGS_INNER_DIR)
+            .join(self.config_file_name())
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Backups don't end with `.caddy`, so they are never imported
+    fn config_backup_path(&self) -> String {
+        format!("{}.bak", self.config_path())
+    }
+}
+
+/// How domains are served
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Site {
+    /// The configured domains with automatic public certificates
+    Public,
+
+    /// `*.localhost` variants of the domains with certificates of caddy's
+    /// internal CA, reachable through the given host port
+    Local { https_port: u16 },
+}
+
+impl Site {
+    fn domain_of(&self, domain: &str) -> String {
+        match self {
+            Site::Public => domain.to_owned(),
+            Site::Local { .. } if domain.ends_with(LOCAL_DOMAIN_SUFFIX) => domain.to_owned(),
+            Site::Local { .. } => format!("{domain}{LOCAL_DOMAIN_SUFFIX}"),
+        }
+    }
+
+    /// Origin as seen from the browser
+    fn origin_of(&self, domain: &str) -> String {
+        match self {
+            Site::Public => format!("https://{}", self.domain_of(domain)),
+            Site::Local { https_port } => {
+                format!("https://{}:{https_port}", self.domain_of(domain))
+            }
+        }
+    }
 }
 
 impl ProxyService {
-    pub fn from_context(context: &context::Context) -> Self {
-        let app_service_container_name = context.container_name_of(services::ServiceKind::App);
+    pub async fn from_context(
+        context: &context::Context,
+        docker: &bollard::Docker,
+    ) -> Result<Self> {
         let name = context.container_name_of(SERVICE_KIND);
 
-        let configs = context.app_config().proxy(context.override_context());
+        // the ports of a running local proxy are kept, otherwise its config
+        // would change on every run and it would be recreated
+        let existing_host_ports = if context.is_local_proxy() {
+            docker::existing_host_ports(docker, &name).await?
+        } else {
+            HashMap::new()
+        };
 
-        let bindings = PORT_MAPPINGS
-            .iter()
-            .map(|(host, inner)| context::HostPortBinding::manual(*host, "0.0.0.0", *inner, &name))
+        let apps = context
+            .app_routes()
+            .into_iter()
+            .map(|(container_name, configs)| ProxiedApp {
+                container_name,
+                configs: configs.to_vec(),
+            })
             .collect();
 
-        let configs = configs
+        let bindings = PORT_MAPPINGS
             .iter()
-            .map(|config| ProxyServiceConfig {
-                domain: config.domain.clone(),
-                port: config.port,
+            .map(|(host, inner)| {
+                if context.is_local_proxy() {
+                    let host_port = existing_host_ports
+                        .get(inner)
+                        .copied()
+                        .unwrap_or_else(free_port);
+
+                    context::HostPortBinding::manual(host_port, "127.0.0.1", *inner, &name)
+                } else {
+                    context::HostPortBinding::manual(*host, "0.0.0.0", *inner, &name)
+                }
             })
-            .collect();
+            .collect::<Vec<_>>();
+
+        let site = if context.is_local_proxy() {
+            let https_port = bindings
+                .iter()
+                .find(|binding| binding.internal_port() == HTTPS_PORT)
+                .and_then(|binding| binding.host_port())
+                .unwrap_or(HTTPS_PORT);
+
+            Site::Local { https_port }
+        } else {
+            Site::Public
+        };
+
+        let image_config = context.app_config().proxy_image(context.override_context());
 
-        Self {
+        Ok(Self {
             name,
-            app_service_container_name,
+            image_name: image_config
+                .and_then(|config| config.image())
+                .unwrap_or(DEFAULT_IMAGE_NAME)
+                .to_owned(),
+            image_tag: image_config
+                .and_then(|config| config.version())
+                .unwrap_or(DEFAULT_IMAGE_TAG)
+                .to_owned(),
+            apps,
             bindings,
-            configs,
-        }
+            site,
+        })
     }
 
     pub async fn post_up(&self, docker: &bollard::Docker) -> Result<()> {
@@ -69,104 +167,412 @@ impl ProxyService {
             return Ok(());
         }
 
-        // in case no proxy configs were specified, we need to remove the configs
-        // in order to close the proxy to the app (in case user deleted it without
-        // stopping)
-        if self.configs.is_empty() {
-            self.delete_configs(docker).await?;
-        } else {
-            self.put_configs(docker).await?;
+        for app in &self.apps {
+            // in case no proxy configs were specified, we need to remove the configs
+            // in order to close the proxy to the app (in case user deleted it without
+            // stopping)
+            if app.configs.is_empty() {
+                self.update_configs(docker, app, None).await?;
+            } else {
+                let upstream = self.resolve_upstream(docker, app).await?;
+                self.update_configs(docker, app, Some(&upstream)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Points the routes of the app to the given host and reloads the proxy
+    pub async fn switch_upstream(
+        &self,
+        docker: &bollard::Docker,
+        app_container_name: &str,
+        upstream: &str,
+    ) -> Result<()> {
+        let Some(app) = self.app_of(app_container_name) else {
+            return Ok(());
+        };
+
+        let is_running = docker::check_container_running(docker, &self.name).await?;
+        if !is_running || app.configs.is_empty() {
+            return Ok(());
         }
 
-        self.reload_caddy(docker).await?;
+        self.update_configs(docker, app, Some(upstream)).await?;
 
         Ok(())
     }
 
+    /// Whether the app's routes point to the upstream, i.e. the container
+    /// deployed under that alias is the one serving traffic
+    pub async fn is_routed_to(
+        &self,
+        docker: &bollard::Docker,
+        app_container_name: &str,
+        upstream: &str,
+    ) -> Result<bool> {
+        let Some(app) = self.app_of(app_container_name) else {
+            return Ok(false);
+        };
+
+        let is_running = docker::check_container_running(docker, &self.name).await?;
+        if !is_running {
+            return Ok(false);
+        }
+
+        let command = format!("cat {} 2> /dev/null; true", app.config_path());
+        let output = docker::exec_command_output(docker, &self.name, &command).await?;
+
+        Ok(output
+            .stdout
+            .contains(&format!("reverse_proxy {upstream}:")))
+    }
+
     pub async fn post_down(&self, docker: &bollard::Docker) -> Result<()> {
+        self.unroute(docker, &self.apps.iter().collect::<Vec<_>>())
+            .await
+    }
+
+    /// Removes the routes of a single app, e.g. when only it was stopped
+    pub async fn post_down_app(
+        &self,
+        docker: &bollard::Docker,
+        app_container_name: &str,
+    ) -> Result<()> {
+        let apps = self
+            .app_of(app_container_name)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        self.unroute(docker, &apps).await
+    }
+
+    async fn unroute(&self, docker: &bollard::Docker, apps: &[&ProxiedApp]) -> Result<()> {
         let is_running = docker::check_container_running(docker, &self.name).await?;
         if !is_running {
             return Ok(());
         }
 
-        self.delete_configs(docker).await?;
-        self.reload_caddy(docker).await?;
+        for app in apps {
+            self.update_configs(docker, app, None).await?;
+        }
+
+        // the last app using the proxy is gone, so ports 80 and 443 are released
+        if self.site_files(docker).await?.is_empty() {
+            presentation::print_proxy_removing(&self.name);
+            self.remove(docker).await?;
+        }
 
         Ok(())
     }
 
+    fn app_of(&self, app_container_name: &str) -> Option<&ProxiedApp> {
+        self.apps
+            .iter()
+            .find(|app| app.container_name == app_container_name)
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    async fn delete_configs(&self, docker: &bollard::Docker) -> Result<()> {
-        let config_path = self.service_config_path();
-        let command = format!("rm {config_path}");
-        docker::exec_command_detached(docker, &self.name, &command).await?;
+    /// Names of the `.caddy` files of all apps using the proxy.
+    /// The proxy must be running
+    pub async fn site_files(&self, docker: &bollard::Docker) -> Result<Vec<String>> {
+        let command = format!(
+            r#"for file in {CADDY_CONFIGS_INNER_DIR}/*.caddy; do [ -f "$file" ] && basename "$file"; done; true"#
+        );
+        let output = docker::exec_command_output(docker, &self.name, &command).await?;
+
+        Ok(output
+            .stdout
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(ToOwned::to_owned)
+            .collect())
+    }
+
+    pub async fn restart(&self, docker: &bollard::Docker) -> Result<()> {
+        docker
+            .restart_container(
+                &self.name,
+                None::<bollard::query_parameters::RestartContainerOptions>,
+            )
+            .await?;
 
         Ok(())
     }
 
-    async fn put_configs(&self, docker: &bollard::Docker) -> Result<()> {
-        let config_contents = self.service_config_contents();
-        let config_path = self.service_config_path();
-        let command = format!(r#"echo "{config_contents}" > {config_path}"#,);
-        docker::exec_command_detached(docker, &self.name, &command).await?;
+    /// Stops and removes the container. Site files stay in the configs volume
+    pub async fn remove(&self, docker: &bollard::Docker) -> Result<()> {
+        docker::remove_container_if_exists(docker, &self.name).await
+    }
+
+    /// Puts the app's configs pointing to the upstream, or deletes them without one,
+    /// then validates and reloads the proxy. If either fails, the previous configs are restored
+    async fn update_configs(
+        &self,
+        docker: &bollard::Docker,
+        app: &ProxiedApp,
+        upstream: Option<&str>,
+    ) -> Result<()> {
+        self.backup_configs(docker, app).await?;
+
+        match upstream {
+            Some(upstream) => self.put_configs(docker, app, upstream).await?,
+            None => self.delete_configs(docker, app).await?,
+        }
+
+        if let Err(error) = self.validate_caddy(docker).await {
+            self.restore_configs(docker, app).await?;
+
+            bail!("Proxy configs are invalid, previous configs were restored.\n{error}");
+        }
+
+        if let Err(error) = self.reload_caddy(docker).await {
+            self.restore_configs(docker, app).await?;
+            // the previous configs were loaded before, so this reload is expected to pass
+            self.reload_caddy(docker).await?;
+
+            bail!("Could not reload the proxy, previous configs were restored.\n{error}");
+        }
 
         Ok(())
     }
 
+    async fn backup_configs(&self, docker: &bollard::Docker, app: &ProxiedApp) -> Result<()> {
+        let config_path = app.config_path();
+        let backup_path = app.config_backup_path();
+        let command = format!(
+            "if [ -f {config_path} ]; then cp {config_path} {backup_path}; else rm -f {backup_path}; fi"
+        );
+
+        self.exec(docker, &command).await
+    }
+
+    async fn restore_configs(&self, docker: &bollard::Docker, app: &ProxiedApp) -> Result<()> {
+        let config_path = app.config_path();
+        let backup_path = app.config_backup_path();
+        let command = format!(
+            "if [ -f {backup_path} ]; then mv {backup_path} {config_path}; else rm -f {config_path}; fi"
+        );
+
+        self.exec(docker, &command).await
+    }
+
+    async fn delete_configs(&self, docker: &bollard::Docker, app: &ProxiedApp) -> Result<()> {
+        let config_path = app.config_path();
+        let command = format!("rm -f {config_path}");
+
+        self.exec(docker, &command).await
+    }
+
+    /// The app container stores the alias it was deployed under in a label,
+    /// otherwise it's reachable by its name
+    async fn resolve_upstream(&self, docker: &bollard::Docker, app: &ProxiedApp) -> Result<String> {
+        let upstream = docker::inspect_container(docker, &app.container_name)
+            .await?
+            .and_then(|container| container.config)
+            .and_then(|config| config.labels)
+            .and_then(|mut labels| labels.remove(services::UPSTREAM_LABEL))
+            .unwrap_or_else(|| app.container_name.clone());
+
+        Ok(upstream)
+    }
+
+    /// The config is uploaded under a temporary name first and then renamed,
+    /// so caddy never sees a partially written file
+    async fn put_configs(
+        &self,
+        docker: &bollard::Docker,
+        app: &ProxiedApp,
+        upstream: &str,
+    ) -> Result<()> {
+        let config_contents = self.service_config_contents(app, upstream);
+        let config_path = app.config_path();
+        let temporary_file_name = format!(".{}.tmp", app.config_file_name());
+
+        docker::upload_file(
+            docker,
+            &self.name,
+            CADDY_CONFIGS_INNER_DIR,
+            &temporary_file_name,
+            config_contents.as_bytes(),
+        )
+        .await?;
+
+        let command =
+            format!("mv -f {CADDY_CONFIGS_INNER_DIR}/{temporary_file_name} {config_path}");
+
+        self.exec(docker, &command).await
+    }
+
+    async fn validate_caddy(&self, docker: &bollard::Docker) -> Result<()> {
+        let caddy_config_path = self.caddy_config_inner_file();
+        let command = format!("caddy validate -c {caddy_config_path} --adapter caddyfile");
+
+        self.exec(docker, &command).await
+    }
+
     async fn reload_caddy(&self, docker: &bollard::Docker) -> Result<()> {
         let caddy_config_path = self.caddy_config_inner_file();
         let command = format!("caddy reload -c {caddy_config_path}");
-        docker::exec_command_detached(docker, &self.name, &command).await?;
+
+        self.exec(docker, &command).await
+    }
+
+    /// Fails with the command's stderr if it exits with a non-zero code
+    async fn exec(&self, docker: &bollard::Docker, command: &str) -> Result<()> {
+        let output = docker::exec_command_detached(docker, &self.name, command).await?;
+
+        if !output.is_success() {
+            bail!(
+                "`{command}` failed in {} with exit code {}:\n{}",
+                self.name,
+                output.exit_code,
+                output.stderr.trim()
+            );
+        }
 
         Ok(())
     }
 
-    fn service_config_contents(&self) -> String {
-        self.configs
+    fn service_config_contents(&self, app: &ProxiedApp, upstream: &str) -> String {
+        app.configs
             .iter()
-            .map(|config| self.https_config(config))
+            .map(|config| https_config(config, upstream, self.site))
             .join("\n")
             .trim()
             .to_owned()
     }
 
-    fn service_config_path(&self) -> String {
-        let Self {
-            app_service_container_name,
-            ..
-        } = self;
+    fn caddy_config_inner_file(&self) -> String {
+        format!("{CADDY_CONFIGS_INNER_DIR}/{CADDY_CONFIG_INNER_FILE}")
+    }
+}
 
-        PathBuf::from(CADDY_CONFIGS_INNER_DIR)
-            .join(format!("{app_service_container_name}.caddy"))
-            .to_string_lossy()
-            .to_string()
+/// Site block of the domain, followed by the `www` redirect if enabled
+fn https_config(config: &config::ProxyConfig, upstream: &str, site: Site) -> String {
+    let domain = site.domain_of(&config.domain);
+    let mut lines = vec![format!("{domain}:443, {domain}:80 {{")];
+
+    if let Site::Local { .. } = site {
+        lines.push("\ttls internal".to_owned());
     }
 
-    fn https_config(&self, config: &ProxyServiceConfig) -> String {
-        let ProxyServiceConfig { domain, port } = config;
-        let Self {
-            app_service_container_name,
-            ..
-        } = self;
+    if let Some(max_body_size) = &config.max_body_size {
+        lines.push("\trequest_body {".to_owned());
+        lines.push(format!("\t\tmax_size {max_body_size}"));
+        lines.push("\t}".to_owned());
+    }
 
-        format!(
-            "{domain}:443, {domain}:80 {{ \nreverse_proxy {app_service_container_name}:{port}\n }}",
-        )
+    if let Some(basic_auth) = &config.basic_auth {
+        lines.push("\tbasic_auth {".to_owned());
+        lines.push(format!(
+            "\t\t{} {}",
+            quote(&basic_auth.user),
+            quote(&basic_auth.password_hash)
+        ));
+        lines.push("\t}".to_owned());
     }
 
-    fn caddy_config_inner_file(&self) -> String {
-        format!("{CADDY_CONFIGS_INNER_DIR}/{CADDY_CONFIG_INNER_FILE}")
+    if !config.headers.is_empty() {
+        lines.push("\theader {".to_owned());
+        lines.extend(
+            config
+                .headers
+                .iter()
+                .map(|(name, value)| format!("\t\t{name} {}", quote(value))),
+        );
+        lines.push("\t}".to_owned());
+    }
+
+    if config.routes.is_empty() {
+        lines.extend(reverse_proxy(config, upstream, config.port, "\t"));
+    } else {
+        // `handle` takes a single matcher token, so both the path itself
+        // and everything below it go into a named matcher.
+        // `handle` blocks are mutually exclusive and the block without a matcher
+        // is sorted last, so it only receives the rest
+        for (index, route) in config.routes.iter().enumerate() {
+            let path = route.path.trim_end_matches('/');
+            let matcher = format!("@route_{index}");
+
+            lines.push(format!("\t{matcher} path {path} {path}/*"));
+            lines.push(format!("\thandle {matcher} {{"));
+
+            if route.strip_prefix {
+                lines.push(format!("\t\turi strip_prefix {path}"));
+            }
+
+            lines.extend(reverse_proxy(config, upstream, route.port, "\t\t"));
+            lines.push("\t}".to_owned());
+        }
+
+        lines.push("\thandle {".to_owned());
+        lines.extend(reverse_proxy(config, upstream, config.port, "\t\t"));
+        lines.push("\t}".to_owned());
+    }
+
+    lines.push("}".to_owned());
+
+    if config.redirect_www {
+        lines.push(format!("www.{domain}:443, www.{domain}:80 {{"));
+
+        if let Site::Local { .. } = site {
+            lines.push("\ttls internal".to_owned());
+        }
+
+        lines.push(format!(
+            "\tredir {}{{uri}} permanent",
+            site.origin_of(&config.domain)
+        ));
+        lines.push("}".to_owned());
     }
+
+    lines.join("\n")
+}
+
+fn reverse_proxy(
+    config: &config::ProxyConfig,
+    upstream: &str,
+    port: u16,
+    indent: &str,
+) -> Vec<String> {
+    match &config.websocket_timeout {
+        Some(timeout) => vec![
+            format!("{indent}reverse_proxy {upstream}:{port} {{"),
+            format!("{indent}\tstream_timeout {timeout}"),
+            format!("{indent}}}"),
+        ],
+        None => vec![format!("{indent}reverse_proxy {upstream}:{port}")],
+    }
+}
+
+/// Quoted Caddyfile token, so values with spaces or quotes stay a single token
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
 }
 
 impl services::ConnectionInfo for ProxyService {
     fn connection_info(&self) -> Vec<String> {
-        self.configs
+        self.apps
             .iter()
-            .map(|ProxyServiceConfig { domain, port }| format!("{domain} -> {port}"))
+            .flat_map(|app| &app.configs)
+            .flat_map(|config| {
+                let origin = match self.site {
+                    Site::Public => config.domain.clone(),
+                    Site::Local { .. } => self.site.origin_of(&config.domain),
+                };
+
+                config
+                    .routes
+                    .iter()
+                    .map(|route| format!("{origin}{} -> {}", route.path, route.port))
+                    .chain(std::iter::once(format!("{origin} -> {}", config.port)))
+                    .collect::<Vec<_>>()
+            })
             .collect()
     }
 }
@@ -176,57 +582,208 @@ impl services::ToContainerConfig for ProxyService {
         let name = context.container_name_of(SERVICE_KIND);
         let config_file = self.caddy_config_inner_file();
 
-        let mut config = bollard::container::Config {
-            image: Some(IMAGE_NAME.to_owned()),
+        let host_config = bollard::models::HostConfig {
+            mounts: Some(
+                VOLUMES_MAPPINGS
+                    .iter()
+                    .map(|(host, inner)| match self.site {
+                        Site::Public => context.manual_mount(host, inner),
+                        // local proxies are per project, so they keep their own data
+                        Site::Local { .. } => context.mount(SERVICE_KIND, inner),
+                    })
+                    .collect(),
+            ),
+            port_bindings: Some(context::HostPortBinding::to_port_bindings(
+                &self.bindings.iter().collect::<Vec<_>>(),
+            )),
+
+            restart_policy: Some(bollard::models::RestartPolicy {
+                name: Some(bollard::models::RestartPolicyNameEnum::ALWAYS),
+                ..Default::default()
+            }),
+
+            ..Default::default()
+        };
+
+        let config = bollard::models::ContainerCreateBody {
+            image: Some(format!("{}:{}", self.image_name, self.image_tag)),
             hostname: Some(name.clone()),
             domainname: Some(name.clone()),
+            labels: Some(context.labels_of(SERVICE_KIND)),
 
             cmd: Some(
-                vec![
-                    "sh",
-                    "-c",
-                    &format!(
-                        r#"echo "{GENERAL_CADDYFILE_CONTENTS}" > {config_file} && caddy run --config {config_file} --adapter caddyfile"#,
-                    ),
+                [
+                    "caddy",
+                    "run",
+                    "--config",
+                    &config_file,
+                    "--adapter",
+                    "caddyfile",
                 ]
                 .into_iter()
                 .map(String::from)
                 .collect(),
             ),
 
-            networking_config: Some(bollard::container::NetworkingConfig {
-                endpoints_config: HashMap::from([(
+            networking_config: Some(bollard::models::NetworkingConfig {
+                endpoints_config: Some(HashMap::from([(
                     network::DPLOY_NETWORK.to_owned(),
                     bollard::models::EndpointSettings::default(),
-                )]),
+                )])),
             }),
 
+            host_config: Some(host_config),
+
             ..Default::default()
         };
 
-        let mut host_config = bollard::models::HostConfig::default();
+        // the root config is uploaded before the container starts, as it is read on start
+        Ok(services::ContainerConfig::new(
+            name,
+            self.image_name.clone(),
+            self.image_tag.clone(),
+            config,
+        )
+        .with_files(vec![services::ContainerFile {
+            dir: CADDY_CONFIGS_INNER_DIR.to_owned(),
+            name: CADDY_CONFIG_INNER_FILE.to_owned(),
+            contents: GENERAL_CADDYFILE_CONTENTS.to_owned(),
+        }]))
+    }
+}
 
-        host_config.mounts = Some(
-            VOLUMES_MAPPINGS
-                .iter()
-                .map(|(host, inner)| context.manual_mount(host, inner))
-                .collect(),
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_https_config() {
+        let config = config::ProxyConfig {
+            domain: "example.com".to_owned(),
+            port: 3000,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            https_config(&config, "app", Site::Public),
+            "example.com:443, example.com:80 {\n\treverse_proxy app:3000\n}"
         );
-        host_config.port_bindings = Some(context::HostPortBinding::to_port_bindings(
-            &self.bindings.iter().collect::<Vec<_>>(),
-        ));
+    }
 
-        host_config.restart_policy = Some(bollard::models::RestartPolicy {
-            name: Some(bollard::models::RestartPolicyNameEnum::ALWAYS),
+    #[test]
+    fn test_https_config_with_routes() {
+        let config = config::ProxyConfig {
+            domain: "example.com".to_owned(),
+            port: 3000,
+            routes: vec![
+                config::ProxyRouteConfig {
+                    path: "/api/".to_owned(),
+                    port: 8080,
+                    strip_prefix: false,
+                },
+                config::ProxyRouteConfig {
+                    path: "/admin".to_owned(),
+                    port: 8081,
+                    strip_prefix: true,
+                },
+            ],
             ..Default::default()
-        });
+        };
 
-        config.host_config = Some(host_config);
+        assert_eq!(
+            https_config(&config, "app", Site::Public),
+            [
+                "example.com:443, example.com:80 {",
+                "\t@route_0 path /api /api/*",
+                "\thandle @route_0 {",
+                "\t\treverse_proxy app:8080",
+                "\t}",
+                "\t@route_1 path /admin /admin/*",
+                "\thandle @route_1 {",
+                "\t\turi strip_prefix /admin",
+                "\t\treverse_proxy app:8081",
+                "\t}",
+                "\thandle {",
+                "\t\treverse_proxy app:3000",
+                "\t}",
+                "}",
+            ]
+            .join("\n")
+        );
+    }
 
-        Ok(services::ContainerConfig::new(
-            name,
-            IMAGE_NAME.to_owned(),
-            config,
-        ))
+    #[test]
+    fn test_https_config_with_options() {
+        let config = config::ProxyConfig {
+            domain: "example.com".to_owned(),
+            port: 3000,
+            redirect_www: true,
+            headers: BTreeMap::from([
+                ("X-Frame-Options".to_owned(), "DENY".to_owned()),
+                (
+                    "Content-Security-Policy".to_owned(),
+                    r#"default-src 'self'; img-src "data""#.to_owned(),
+                ),
+            ]),
+            basic_auth: Some(config::BasicAuthConfig {
+                user: "admin".to_owned(),
+                password_hash: "$2a$14$hash".to_owned(),
+            }),
+            max_body_size: Some("10MB".to_owned()),
+            websocket_timeout: Some("1h".to_owned()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            https_config(&config, "app", Site::Public),
+            [
+                "example.com:443, example.com:80 {",
+                "\trequest_body {",
+                "\t\tmax_size 10MB",
+                "\t}",
+                "\tbasic_auth {",
+                "\t\t\"admin\" \"$2a$14$hash\"",
+                "\t}",
+                "\theader {",
+                r#"		Content-Security-Policy "default-src 'self'; img-src \"data\"""#,
+                "\t\tX-Frame-Options \"DENY\"",
+                "\t}",
+                "\treverse_proxy app:3000 {",
+                "\t\tstream_timeout 1h",
+                "\t}",
+                "}",
+                "www.example.com:443, www.example.com:80 {",
+                "\tredir https://example.com{uri} permanent",
+                "}",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_https_config_local() {
+        let config = config::ProxyConfig {
+            domain: "example.com".to_owned(),
+            port: 3000,
+            redirect_www: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            https_config(&config, "app", Site::Local { https_port: 8443 }),
+            [
+                "example.com.localhost:443, example.com.localhost:80 {",
+                "\ttls internal",
+                "\treverse_proxy app:3000",
+                "}",
+                "www.example.com.localhost:443, www.example.com.localhost:80 {",
+                "\ttls internal",
+                "\tredir https://example.com.localhost:8443{uri} permanent",
+                "}",
+            ]
+            .join("\n")
+        );
     }
 }
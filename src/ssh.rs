@@ -1,6 +1,6 @@
 use std::{net::TcpListener, path::Path, time::Duration};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use bollard::{Docker, API_DEFAULT_VERSION};
 use openssh::{ForwardType, KnownHosts, Session, SessionBuilder};
 
@@ -8,11 +8,11 @@ use crate::{context, presentation};
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
 
-pub async fn get_remote_docker_client(context: &context::Context) -> Result<(Docker, Session)> {
-    let Some(credentials) = context.ssh_credentials() else {
-        bail!("No SSH credentials provided")
-    };
-
+/// `quiet` skips the connection messages, e.g. when stdout must only contain JSON
+pub async fn get_remote_docker_client(
+    credentials: &context::SshCredentials,
+    quiet: bool,
+) -> Result<(Docker, Session)> {
     let mut builder = SessionBuilder::default();
 
     builder
@@ -31,14 +31,20 @@ pub async fn get_remote_docker_client(context: &context::Context) -> Result<(Doc
         listener.local_addr()?
     };
 
-    presentation::print_remote_host_connecting(credentials.host());
+    if !quiet {
+        presentation::print_remote_host_connecting(credentials.host());
+    }
+
     let temp_dir = builder
         .launch_master(credentials.host())
         .await
         .context("Could not launch ssh")?;
 
     let session = Session::new_process_mux(temp_dir);
-    presentation::print_remote_host_success(credentials.host());
+
+    if !quiet {
+        presentation::print_remote_host_success(credentials.host());
+    }
 
     let socket_path = Path::new("/var/run/docker.sock");
     session
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+/// Parses durations like `30s`, `15m`, `12h` or `7d`
+pub fn parse(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let unit_index = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(unit_index);
+
+    let amount = amount
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid duration `{value}`, expected e.g. `7d` or `12h`"))?;
+
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit `{unit}`, expected one of s, m, h, d")),
+    };
+
+    let total = amount
+        .checked_mul(seconds)
+        .ok_or_else(|| format!("Duration `{value}` is too long"))?;
+
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse("12h"), Ok(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse("7d"), Ok(Duration::from_secs(7 * 86400)));
+        assert!(parse("7").is_err());
+        assert!(parse("d").is_err());
+        assert!(parse("7w").is_err());
+        assert!(parse("99999999999999999d").is_err());
+    }
+}
@@ -1,3 +1,3 @@
-pub mod file;
+pub mod duration;
 pub mod network;
 pub mod string;